@@ -0,0 +1,130 @@
+use crate::tuning;
+use bevy::prelude::Color;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum WolfKind {
+    #[default]
+    Regular,
+    /// Slow and barely visible
+    Sneaky,
+    /// Fast but gets tired quickly
+    Runner,
+    /// Needs two clicks before running away
+    Bold,
+    /// Scares animals from much further away
+    Howler,
+}
+
+/// Read from `assets/tuning/wolves.ron`
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct WolfStats {
+    pub tint: Color,
+    pub scale: f32,
+    pub roam_speed: f32,
+    pub hunt_speed: f32,
+    pub flee_speed: f32,
+    pub carry_speed: f32,
+    /// Radius within which happy animals get scared
+    pub scare_radius: f32,
+    /// Seconds a wolf can chase before getting tired
+    #[serde(default = "tireless")]
+    pub stamina: f64,
+    pub clicks_to_scare: u32,
+}
+
+fn tireless() -> f64 {
+    f64::INFINITY
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub(crate) struct WolfArchetypes {
+    regular: WolfStats,
+    sneaky: WolfStats,
+    runner: WolfStats,
+    bold: WolfStats,
+    howler: WolfStats,
+}
+
+/// Same stats as the shipped `wolves.ron`, used when it can't be read
+impl Default for WolfArchetypes {
+    fn default() -> Self {
+        let regular = WolfStats {
+            tint: Color::WHITE,
+            scale: 1.0,
+            roam_speed: 60.0,
+            hunt_speed: 100.0,
+            flee_speed: 180.0,
+            carry_speed: 70.0,
+            scare_radius: 150.0,
+            stamina: tireless(),
+            clicks_to_scare: 1,
+        };
+        WolfArchetypes {
+            sneaky: WolfStats {
+                tint: Color::rgba(0.7, 0.7, 0.8, 0.35),
+                scale: 0.9,
+                roam_speed: 40.0,
+                hunt_speed: 70.0,
+                flee_speed: 160.0,
+                carry_speed: 60.0,
+                scare_radius: 80.0,
+                ..regular
+            },
+            runner: WolfStats {
+                tint: Color::rgb(1.0, 0.85, 0.6),
+                scale: 0.85,
+                roam_speed: 80.0,
+                hunt_speed: 170.0,
+                flee_speed: 220.0,
+                carry_speed: 90.0,
+                stamina: 4.0,
+                ..regular
+            },
+            bold: WolfStats {
+                tint: Color::rgb(0.6, 0.5, 0.5),
+                scale: 1.25,
+                hunt_speed: 95.0,
+                flee_speed: 160.0,
+                scare_radius: 170.0,
+                clicks_to_scare: 2,
+                ..regular
+            },
+            howler: WolfStats {
+                tint: Color::rgb(0.7, 0.8, 1.0),
+                scale: 1.05,
+                hunt_speed: 90.0,
+                scare_radius: 280.0,
+                ..regular
+            },
+            regular,
+        }
+    }
+}
+
+static ARCHETYPES: OnceLock<WolfArchetypes> = OnceLock::new();
+
+impl WolfKind {
+    pub fn stats(&self) -> &'static WolfStats {
+        let all = ARCHETYPES.get_or_init(|| tuning::parse("wolves", tuning::WOLVES));
+        match self {
+            WolfKind::Regular => &all.regular,
+            WolfKind::Sneaky => &all.sneaky,
+            WolfKind::Runner => &all.runner,
+            WolfKind::Bold => &all.bold,
+            WolfKind::Howler => &all.howler,
+        }
+    }
+
+    /// Picks the kind of a pack follower, most of them are regular wolves
+    pub fn random_follower() -> WolfKind {
+        match fastrand::u32(0..10) {
+            0..=4 => WolfKind::Regular,
+            5 | 6 => WolfKind::Runner,
+            7 | 8 => WolfKind::Sneaky,
+            _ => WolfKind::Howler,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::entities::{Chicken, Dog, Looker, LookerState, NNTree, ScaredBubble};
+use crate::gfx::{Action, Inputs, MouseProj};
+use crate::panic::PanicSpread;
+use crate::upgrades::Upgrades;
+use bevy::math::{vec3, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+
+const CALM_RADIUS: f32 = 30.0;
+
+#[derive(Component)]
+pub struct Hearts {
+    until: f64,
+}
+
+// Hovering a scared animal for a while, or clicking it, makes it happy again
+pub fn calm_animals(
+    mut commands: Commands,
+    time: Res<Time>,
+    inputs: Res<Inputs>,
+    hand: Res<MouseProj>,
+    tree: Res<NNTree>,
+    upgrades: Res<Upgrades>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    mut hovered: Local<Option<(Entity, f64)>>,
+    mut lookers: Query<(&mut Looker, &Children), Or<(With<Dog>, With<Chicken>)>>,
+    childs: Query<&Children>,
+    bubbles: Query<(), With<ScaredBubble>>,
+) {
+    let now = time.seconds_since_startup();
+
+    let mut nearest = None;
+    let mut neares_dist = f32::INFINITY;
+    for (pos, e) in tree.within_distance(hand.0.extend(0.0), CALM_RADIUS) {
+        let d = pos.xy().distance_squared(hand.0);
+        let scared = matches!(
+            lookers.get(e),
+            Ok((l, _)) if matches!(l.state, LookerState::Scared { .. } | LookerState::ScaredInside { .. })
+        );
+        if scared && d < neares_dist {
+            neares_dist = d;
+            nearest = Some(e);
+        }
+    }
+
+    let ent = match nearest {
+        Some(x) => x,
+        None => {
+            *hovered = None;
+            return;
+        }
+    };
+
+    let since = match *hovered {
+        Some((e, since)) if e == ent => since,
+        _ => {
+            *hovered = Some((ent, now));
+            now
+        }
+    };
+
+    if !inputs.just_pressed.contains(&Action::ClickLeft) && now - since < upgrades.calm_hover_time()
+    {
+        return;
+    }
+    *hovered = None;
+
+    let (mut looker, children) = match lookers.get_mut(ent) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    looker.state = match looker.state {
+        LookerState::ScaredInside { .. } => LookerState::HappyInside,
+        _ => LookerState::Happy,
+    };
+    commands.entity(ent).remove::<PanicSpread>();
+
+    let sprite = children[0];
+    if let Ok(sprite_children) = childs.get(sprite) {
+        for &c in sprite_children.iter() {
+            if bubbles.contains(c) {
+                commands.entity(c).despawn_recursive();
+            }
+        }
+    }
+
+    commands
+        .spawn()
+        .insert(Hearts { until: now + 1.0 })
+        .insert(Parent(sprite))
+        .insert_bundle(SpriteBundle {
+            transform: Transform::default()
+                .with_translation(vec3(0.0, 35.0, 0.01))
+                .with_scale(vec3(0.4, 0.4, 1.0)),
+            texture: asset_server.load("heart.png"),
+            ..Default::default()
+        });
+
+    audio.play_with_settings(
+        asset_server.load("calm.wav"),
+        PlaybackSettings {
+            repeat: false,
+            volume: 0.5,
+            speed: 0.9 + fastrand::f32() * 0.2,
+        },
+    );
+}
+
+// Hearts float up and fade away
+pub fn hearts_update(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut qry: Query<(Entity, &Hearts, &mut Transform, &mut Sprite)>,
+) {
+    let now = time.seconds_since_startup();
+    for (ent, hearts, mut trans, mut sprite) in qry.iter_mut() {
+        let left = (hearts.until - now) as f32;
+        if left < 0.0 {
+            commands.entity(ent).despawn_recursive();
+            continue;
+        }
+        trans.translation.y += 30.0 * time.delta_seconds();
+        sprite.color.set_a(left.min(1.0));
+    }
+}
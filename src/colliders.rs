@@ -0,0 +1,165 @@
+use crate::entities::CarriedBy;
+use crate::physics::Airborne;
+use crate::steering::Velocity;
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+
+/// Radius of the body of every moving agent
+pub(crate) const AGENT_RADIUS: f32 = 10.0;
+const TRUNK_RADIUS: f32 = 7.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Circle {
+        center: Vec2,
+        radius: f32,
+    },
+    /// A fence with no thickness, from `a` to `b`
+    Segment {
+        a: Vec2,
+        b: Vec2,
+    },
+}
+
+impl Shape {
+    pub fn closest_point(&self, p: Vec2) -> Vec2 {
+        match *self {
+            Shape::Circle { center, radius } => center + (p - center).normalize_or_zero() * radius,
+            Shape::Segment { a, b } => {
+                let ab = b - a;
+                let t = ((p - a).dot(ab) / ab.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+                a + ab * t
+            }
+        }
+    }
+
+    fn contains(&self, p: Vec2) -> bool {
+        match *self {
+            Shape::Circle { center, radius } => center.distance_squared(p) < radius * radius,
+            Shape::Segment { .. } => false,
+        }
+    }
+
+    /// Where a body of `radius` at `p` must be moved to stop overlapping, with the push direction
+    pub fn push_out(&self, p: Vec2, radius: f32) -> Option<(Vec2, Vec2)> {
+        let closest = self.closest_point(p);
+        let diff = p - closest;
+        let dist = diff.length();
+        let inside = self.contains(p);
+        if dist >= radius && !inside {
+            return None;
+        }
+        let mut normal = diff.normalize_or_zero();
+        if inside {
+            normal = -normal;
+        }
+        if normal == Vec2::ZERO {
+            normal = vec2(0.0, 1.0);
+        }
+        Some((closest + normal * radius, normal))
+    }
+}
+
+/// Both ends of the opening in the top fence of the pen centered on `center`
+pub fn door_gap(center: Vec2) -> (Vec2, Vec2) {
+    let top = center.y + 128.0;
+    (vec2(center.x - 56.0, top), vec2(center.x + 52.0, top))
+}
+
+/// Solid shapes put on static entities, agents can't go through them
+#[derive(Component)]
+pub struct Collider {
+    pub shapes: Vec<Shape>,
+}
+
+impl Collider {
+    pub fn trunk(base: Vec2) -> Collider {
+        Collider {
+            shapes: vec![Shape::Circle {
+                center: base,
+                radius: TRUNK_RADIUS,
+            }],
+        }
+    }
+
+    /// The four fences of a pen centered on `center`, with a door gap in the top one
+    pub fn pen(center: Vec2) -> Collider {
+        let (left, right) = (center.x - 234.0, center.x + 232.0);
+        let (top, bottom) = (center.y + 128.0, center.y - 226.0);
+        let (door_left, door_right) = door_gap(center);
+        let seg = |a: Vec2, b: Vec2| Shape::Segment { a, b };
+        Collider {
+            shapes: vec![
+                seg(vec2(left, top), door_left),
+                seg(door_right, vec2(right, top)),
+                seg(vec2(left, top), vec2(left, bottom)),
+                seg(vec2(right, top), vec2(right, bottom)),
+                seg(vec2(left, bottom), vec2(right, bottom)),
+            ],
+        }
+    }
+}
+
+/// Every static shape of the map, rebuilt when colliders are added, changed or removed
+#[derive(Default)]
+pub struct Obstacles {
+    pub shapes: Vec<Shape>,
+    /// Bumped on every rebuild so other systems know when to refresh their own caches
+    pub version: u32,
+}
+
+pub fn update_obstacles(
+    mut obstacles: ResMut<Obstacles>,
+    changed: Query<(), Changed<Collider>>,
+    removed: RemovedComponents<Collider>,
+    colliders: Query<&Collider>,
+) {
+    if changed.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+    obstacles.shapes = colliders
+        .iter()
+        .flat_map(|c| c.shapes.iter().copied())
+        .collect();
+    obstacles.version += 1;
+}
+
+// Runs after the agents moved, pushes them out of the obstacles and cancels their velocity into them
+pub fn resolve_collisions(
+    obstacles: Res<Obstacles>,
+    mut agents: Query<(&mut Transform, &mut Velocity), (Without<CarriedBy>, Without<Airborne>)>,
+) {
+    for (mut trans, mut vel) in agents.iter_mut() {
+        let mut pos = trans.translation.xy();
+        for shape in &obstacles.shapes {
+            if let Some((out, normal)) = shape.push_out(pos, AGENT_RADIUS) {
+                pos = out;
+                let into = vel.0.dot(normal).min(0.0);
+                vel.0 -= normal * into;
+            }
+        }
+        trans.translation.x = pos.x;
+        trans.translation.y = pos.y;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_push_out() {
+    let fence = Shape::Segment {
+        a: vec2(0.0, 0.0),
+        b: vec2(100.0, 0.0),
+    };
+    assert_eq!(fence.push_out(vec2(50.0, 20.0), 10.0), None);
+    let (p, n) = fence.push_out(vec2(50.0, -4.0), 10.0).unwrap();
+    assert_eq!(p, vec2(50.0, -10.0));
+    assert_eq!(n, vec2(0.0, -1.0));
+    assert_eq!(fence.push_out(vec2(115.0, 0.0), 10.0), None);
+
+    let trunk = Shape::Circle {
+        center: vec2(0.0, 0.0),
+        radius: 5.0,
+    };
+    let (p, _) = trunk.push_out(vec2(3.0, 0.0), 10.0).unwrap();
+    assert_eq!(p, vec2(15.0, 0.0));
+}
@@ -0,0 +1,594 @@
+use crate::entities::{start_game, DespawnQry};
+use crate::fences::{fence_transform, FenceBudget, FenceRules};
+use crate::gfx::{Action, Inputs, MouseProj};
+use crate::mapgen::{validate, MapError};
+use crate::maps::{Area, FlowerPatch, Forest, Map, MapList, Obstacle, Pen, PenKind, Terrain};
+use crate::scoring::Score;
+use crate::upgrades::Upgrades;
+use crate::waves::WaveDirector;
+use crate::GameState;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+/// Edits kept to be undone
+const MAX_UNDO: usize = 50;
+/// How close to an obstacle a click must be to erase it
+const ERASE_DISTANCE: f32 = 40.0;
+/// Everything placed by the editor is aligned on this grid
+const SNAP: f32 = 10.0;
+/// Shorter drags are clicks
+const MIN_DRAG: f32 = 10.0;
+const OUTLINE_WIDTH: f32 = 6.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EditTool {
+    Forest,
+    Flowers,
+    Pen(PenKind),
+    Fence,
+    Hedge,
+    Pond,
+    Tree,
+    Mud,
+    TallGrass,
+    River,
+    Bridge,
+    DogSpawn,
+    ChickenSpawn,
+    WolfSpawn,
+    Meadow,
+    Camera,
+    Erase,
+}
+
+pub const ALL_EDIT_TOOLS: [EditTool; 19] = [
+    EditTool::Forest,
+    EditTool::Flowers,
+    EditTool::Pen(PenKind::Dog),
+    EditTool::Pen(PenKind::Chicken),
+    EditTool::Pen(PenKind::DogChick),
+    EditTool::Fence,
+    EditTool::Hedge,
+    EditTool::Pond,
+    EditTool::Tree,
+    EditTool::Mud,
+    EditTool::TallGrass,
+    EditTool::River,
+    EditTool::Bridge,
+    EditTool::DogSpawn,
+    EditTool::ChickenSpawn,
+    EditTool::WolfSpawn,
+    EditTool::Meadow,
+    EditTool::Camera,
+    EditTool::Erase,
+];
+
+impl EditTool {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EditTool::Forest => "Forest",
+            EditTool::Flowers => "Flowers",
+            EditTool::Pen(PenKind::Dog) => "Dog pen",
+            EditTool::Pen(PenKind::Chicken) => "Chicken pen",
+            EditTool::Pen(PenKind::DogChick) => "Dogchick pen",
+            EditTool::Fence => "Fence",
+            EditTool::Hedge => "Hedge",
+            EditTool::Pond => "Pond",
+            EditTool::Tree => "Tree",
+            EditTool::Mud => "Mud",
+            EditTool::TallGrass => "Tall grass",
+            EditTool::River => "River",
+            EditTool::Bridge => "Bridge",
+            EditTool::DogSpawn => "Dog spawn",
+            EditTool::ChickenSpawn => "Chicken spawn",
+            EditTool::WolfSpawn => "Wolf forest",
+            EditTool::Meadow => "Meadow",
+            EditTool::Camera => "Camera bounds",
+            EditTool::Erase => "Erase",
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            EditTool::Pen(_) => "Click where the middle of the pen goes",
+            EditTool::Tree => "Click to plant a lone tree",
+            EditTool::Bridge => "Click on a river to put a bridge over it",
+            EditTool::River => "Drag a rectangle, the river flows along its longest side",
+            EditTool::Erase => {
+                "Click an obstacle, pen, flower patch, terrain or forest to remove it"
+            }
+            EditTool::Fence | EditTool::Hedge => "Drag from one end to the other",
+            EditTool::Pond => "Drag from the middle of the pond to its shore",
+            _ => "Drag a rectangle",
+        }
+    }
+
+    fn drags(&self) -> bool {
+        !matches!(
+            self,
+            EditTool::Pen(_) | EditTool::Tree | EditTool::Bridge | EditTool::Erase
+        )
+    }
+}
+
+pub struct MapEditor {
+    pub tool: EditTool,
+    undo: Vec<Map>,
+    redo: Vec<Map>,
+    drag_from: Option<Vec2>,
+    /// The edited map is being played, the editor comes back when it is over
+    pub playtesting: bool,
+    /// Name the map is saved under
+    name: String,
+    /// Outcome of the last save
+    status: Option<String>,
+    /// Validation of the edited map, computed again when it changes
+    check: Option<Result<(), MapError>>,
+}
+
+impl Default for MapEditor {
+    fn default() -> Self {
+        MapEditor {
+            tool: EditTool::Forest,
+            undo: vec![],
+            redo: vec![],
+            drag_from: None,
+            playtesting: false,
+            name: String::new(),
+            status: None,
+            check: None,
+        }
+    }
+}
+
+impl MapEditor {
+    /// Applies the current tool to `map`, the map before the edit can be undone
+    pub fn edit(&mut self, map: &mut Map, from: Vec2, to: Vec2) -> bool {
+        let before = map.clone();
+        if !apply(self.tool, map, snap(from), snap(to)) {
+            return false;
+        }
+        // The seed of a generated map no longer gives the edited layout
+        map.seed = None;
+        self.undo.push(before);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        true
+    }
+
+    pub fn undo(&mut self, map: &mut Map) {
+        if let Some(prev) = self.undo.pop() {
+            self.redo.push(std::mem::replace(map, prev));
+        }
+    }
+
+    pub fn redo(&mut self, map: &mut Map) {
+        if let Some(next) = self.redo.pop() {
+            self.undo.push(std::mem::replace(map, next));
+        }
+    }
+}
+
+fn snap(p: Vec2) -> Vec2 {
+    (p / SNAP).round() * SNAP
+}
+
+/// Changes `map` with `tool` dragged from `from` to `to`, false when nothing changed
+fn apply(tool: EditTool, map: &mut Map, from: Vec2, to: Vec2) -> bool {
+    if tool.drags() && from.distance(to) < MIN_DRAG {
+        return false;
+    }
+    let area = Area::from_corners(from, to);
+    match tool {
+        EditTool::Forest => map.forests.push(Forest {
+            area,
+            trees: 4.5,
+            bushes: 0.5,
+            backdrop: None,
+        }),
+        EditTool::Flowers => map.flowers.push(FlowerPatch {
+            area,
+            density: 12.0,
+        }),
+        EditTool::Pen(kind) => map.pens.push(Pen::new(kind, to)),
+        EditTool::Fence => map.obstacles.push(Obstacle::Fence(from, to)),
+        EditTool::Hedge => map.obstacles.push(Obstacle::Hedge(from, to)),
+        EditTool::Pond => map.obstacles.push(Obstacle::Pond {
+            center: from,
+            radius: from.distance(to),
+        }),
+        EditTool::Tree => map.obstacles.push(Obstacle::Trunk(to)),
+        EditTool::Mud => map.terrain.push(Terrain::Mud(area)),
+        EditTool::TallGrass => map.terrain.push(Terrain::TallGrass(area)),
+        EditTool::River => map.terrain.push(Terrain::River {
+            area,
+            bridges: vec![],
+        }),
+        EditTool::Bridge => {
+            return map
+                .terrain
+                .iter_mut()
+                .filter(|t| t.area().contains(to))
+                .any(|t| t.add_bridge(to));
+        }
+        EditTool::DogSpawn => map.spawns.dogs = area,
+        EditTool::ChickenSpawn => map.spawns.chickens = area,
+        EditTool::WolfSpawn => map.spawns.wolves = area,
+        EditTool::Meadow => map.meadow = area,
+        EditTool::Camera => map.camera = area,
+        EditTool::Erase => return erase(map, to),
+    }
+    true
+}
+
+/// Removes the obstacle, pen, flower patch, terrain or forest under `p`, in that order
+fn erase(map: &mut Map, p: Vec2) -> bool {
+    if let Some(i) = map
+        .obstacles
+        .iter()
+        .position(|o| o.shape().push_out(p, ERASE_DISTANCE).is_some())
+    {
+        map.obstacles.remove(i);
+    } else if let Some(i) = map.pens.iter().position(|pen| pen.interior().contains(p)) {
+        map.pens.remove(i);
+    } else if let Some(i) = map.flowers.iter().position(|f| f.area.contains(p)) {
+        map.flowers.remove(i);
+    } else if let Some(i) = map.terrain.iter().position(|t| t.area().contains(p)) {
+        map.terrain.remove(i);
+    } else if let Some(i) = map.forests.iter().position(|f| f.area.contains(p)) {
+        map.forests.remove(i);
+    } else {
+        return false;
+    }
+    true
+}
+
+#[derive(Component)]
+pub struct EditorPreview;
+
+/// Outlines of the invisible parts of the map, only shown in the editor
+#[derive(Component)]
+pub struct EditorOverlay;
+
+pub fn spawn_editor_preview(mut commands: Commands) {
+    commands
+        .spawn()
+        .insert(EditorPreview)
+        .insert_bundle(SpriteBundle {
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        });
+}
+
+// Drag or click on the world to edit the map with the current tool
+pub fn editor_tools(
+    inputs: Res<Inputs>,
+    hand: Res<MouseProj>,
+    state: Res<GameState>,
+    mut egui_context: ResMut<EguiContext>,
+    mut map: ResMut<Map>,
+    mut editor: ResMut<MapEditor>,
+    mut preview: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<EditorPreview>>,
+) {
+    let on_panel = egui_context.ctx_mut().wants_pointer_input();
+    if !matches!(*state, GameState::Editor) {
+        editor.drag_from = None;
+    } else if inputs.just_pressed.contains(&Action::ClickLeft) && !on_panel {
+        editor.drag_from = Some(hand.0);
+    }
+
+    let from = match editor.drag_from {
+        Some(from) => snap(from),
+        None => {
+            for (_, _, mut vis) in preview.iter_mut() {
+                vis.is_visible = false;
+            }
+            return;
+        }
+    };
+    let to = snap(hand.0);
+
+    if inputs.pressed.contains(&Action::ClickLeft) {
+        let (t, size) = match editor.tool {
+            EditTool::Fence | EditTool::Hedge => fence_transform(from, to),
+            EditTool::Pond => (
+                Transform::from_translation(from.extend(0.9)),
+                Vec2::splat(from.distance(to) * 2.0),
+            ),
+            _ if editor.tool.drags() => {
+                let area = Area::from_corners(from, to);
+                (
+                    Transform::from_translation(area.center().extend(0.9)),
+                    area.size(),
+                )
+            }
+            _ => (
+                Transform::from_translation(to.extend(0.9)),
+                Vec2::splat(20.0),
+            ),
+        };
+        for (mut trans, mut sprite, mut vis) in preview.iter_mut() {
+            *trans = t;
+            trans.translation.z = 0.9;
+            sprite.custom_size = Some(size);
+            sprite.color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+            vis.is_visible = true;
+        }
+        return;
+    }
+
+    editor.drag_from = None;
+    let mut edited = map.clone();
+    if editor.edit(&mut edited, from, to) {
+        *map = edited;
+    }
+}
+
+fn spawn_rect(commands: &mut Commands, area: &Area, color: Color) {
+    let center = area.center();
+    commands
+        .spawn()
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(area.size()),
+                color,
+                ..Default::default()
+            },
+            transform: Transform::from_translation(center.extend(0.8)),
+            ..Default::default()
+        })
+        .insert(EditorOverlay);
+}
+
+fn spawn_outline(commands: &mut Commands, area: &Area, color: Color) {
+    let (l, r, b, t) = (area.left, area.right, area.bottom, area.top);
+    let w = OUTLINE_WIDTH;
+    for side in [
+        Area::from_corners(vec2(l - w, b - w), vec2(r + w, b)),
+        Area::from_corners(vec2(l - w, t), vec2(r + w, t + w)),
+        Area::from_corners(vec2(l - w, b), vec2(l, t)),
+        Area::from_corners(vec2(r, b), vec2(r + w, t)),
+    ] {
+        spawn_rect(commands, &side, color);
+    }
+}
+
+// Shows the spawn zones, bounds and doors while editing
+pub fn editor_overlay(
+    mut commands: Commands,
+    state: Res<GameState>,
+    map: Res<Map>,
+    old: Query<Entity, With<EditorOverlay>>,
+    mut shown: Local<bool>,
+) {
+    let editing = matches!(*state, GameState::Editor);
+    if editing == *shown && !(editing && map.is_changed()) {
+        return;
+    }
+    *shown = editing;
+    for ent in old.iter() {
+        commands.entity(ent).despawn();
+    }
+    if !editing {
+        return;
+    }
+
+    let spawns = &map.spawns;
+    spawn_rect(&mut commands, &spawns.dogs, Color::rgba(0.6, 0.4, 0.2, 0.3));
+    spawn_rect(
+        &mut commands,
+        &spawns.chickens,
+        Color::rgba(1.0, 0.9, 0.3, 0.3),
+    );
+    spawn_rect(
+        &mut commands,
+        &spawns.wolves,
+        Color::rgba(0.3, 0.3, 0.35, 0.3),
+    );
+    for forest in &map.forests {
+        spawn_outline(&mut commands, &forest.area, Color::rgba(0.1, 0.5, 0.1, 0.6));
+    }
+    for patch in &map.flowers {
+        spawn_outline(&mut commands, &patch.area, Color::rgba(1.0, 0.6, 0.8, 0.6));
+    }
+    spawn_outline(&mut commands, &map.meadow, Color::rgba(0.3, 0.9, 0.3, 0.8));
+    spawn_outline(&mut commands, &map.camera, Color::rgba(0.3, 0.6, 1.0, 0.8));
+    for pen in &map.pens {
+        let door = Area::from_corners(pen.door - Vec2::splat(12.0), pen.door + Vec2::splat(12.0));
+        spawn_rect(&mut commands, &door, Color::rgba(1.0, 0.3, 0.2, 0.8));
+    }
+}
+
+fn describe(err: &MapError) -> String {
+    match err {
+        MapError::MissingPen(kind) => format!("No {:?} pen", kind),
+        MapError::Unreachable(kind) => format!("The {:?} door can't be reached", kind),
+        MapError::MisplacedDoor(kind) => format!("The {:?} door is not at the gap", kind),
+        MapError::PensTooClose(gap) => format!("Dog and chicken doors too close ({:.0})", gap),
+        MapError::PensTooFar(gap) => format!("Dog and chicken doors too far ({:.0})", gap),
+        MapError::Unfair(gap) => format!("Dogs and chickens unequally exposed ({:.0})", gap),
+    }
+}
+
+pub fn editor_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut egui_context: ResMut<EguiContext>,
+    mut state: ResMut<GameState>,
+    mut map: ResMut<Map>,
+    mut maps: ResMut<MapList>,
+    mut editor: ResMut<MapEditor>,
+    mut score: ResMut<Score>,
+    mut director: ResMut<WaveDirector>,
+    mut upgrades: ResMut<Upgrades>,
+    mut fences: ResMut<FenceBudget>,
+    fence_rules: Res<FenceRules>,
+    qry: DespawnQry,
+) {
+    if editor.playtesting && !matches!(*state, GameState::Editor) {
+        egui::Window::new("Playtest")
+            .title_bar(false)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_TOP, (0.0, 0.0))
+            .show(egui_context.ctx_mut(), |ui| {
+                if ui.button("Back to editor").clicked() {
+                    for ent in qry.iter() {
+                        commands.entity(ent).despawn_recursive();
+                    }
+                    editor.playtesting = false;
+                    *state = GameState::Editor;
+                }
+            });
+        return;
+    }
+    if !matches!(*state, GameState::Editor) {
+        return;
+    }
+
+    if editor.name.is_empty() {
+        editor.name = map.name.clone();
+    }
+    if map.is_changed() || editor.check.is_none() {
+        editor.check = Some(validate(&map));
+    }
+
+    let mut newstate = None;
+    egui::SidePanel::left("editor").show(egui_context.ctx_mut(), |ui| {
+        ui.heading("Map editor");
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut editor.name);
+        });
+        ui.separator();
+
+        for tool in ALL_EDIT_TOOLS {
+            if ui
+                .radio(editor.tool == tool, tool.label())
+                .on_hover_text(tool.hint())
+                .clicked()
+            {
+                editor.tool = tool;
+            }
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!editor.undo.is_empty(), egui::Button::new("Undo"))
+                .clicked()
+            {
+                editor.undo(&mut map);
+            }
+            if ui
+                .add_enabled(!editor.redo.is_empty(), egui::Button::new("Redo"))
+                .clicked()
+            {
+                editor.redo(&mut map);
+            }
+        });
+
+        match &editor.check {
+            Some(Err(err)) => ui.colored_label(egui::Color32::LIGHT_RED, describe(err)),
+            _ => ui.label("Ready to play"),
+        };
+
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                let name = editor.name.trim().to_string();
+                if !name.is_empty() && map.name != name {
+                    map.name = name;
+                }
+                editor.status = Some(maps.save(&map).unwrap_or_else(|e| e));
+            }
+            let playable = !matches!(editor.check, Some(Err(_)));
+            if ui
+                .add_enabled(playable, egui::Button::new("Playtest"))
+                .clicked()
+            {
+                editor.playtesting = true;
+                newstate = Some(GameState::Playing);
+                start_game(
+                    qry,
+                    &mut commands,
+                    &asset_server,
+                    &time,
+                    &mut score,
+                    &mut director,
+                    &mut upgrades,
+                    &mut fences,
+                    &fence_rules,
+                    &mut map,
+                );
+                score.ranked = false;
+            }
+        });
+        if let Some(status) = &editor.status {
+            ui.label(status);
+        }
+        ui.separator();
+
+        if ui.button("Back to menu").clicked() {
+            if !maps.random {
+                *map = maps.maps[maps.selected].clone();
+            }
+            *editor = MapEditor::default();
+            newstate = Some(GameState::Menu {
+                leaderboard_load: false,
+                finished_loading: default(),
+                leaderboard_result: default(),
+            });
+        }
+    });
+
+    if let Some(newstate) = newstate {
+        *state = newstate;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_editor() {
+    let mut map = MapList::builtin().maps[0].clone();
+    let mut editor = MapEditor::default();
+    let obstacles = map.obstacles.len();
+
+    editor.tool = EditTool::Pond;
+    assert!(!editor.edit(&mut map, vec2(0.0, 0.0), vec2(2.0, 0.0)));
+    assert!(editor.edit(&mut map, vec2(1.0, -2.0), vec2(61.0, -2.0)));
+    assert!(matches!(
+        map.obstacles.last(),
+        Some(Obstacle::Pond { radius, .. }) if *radius == 60.0
+    ));
+
+    editor.tool = EditTool::Camera;
+    let camera = map.camera;
+    assert!(editor.edit(&mut map, vec2(500.0, 400.0), vec2(-500.0, -400.0)));
+    assert_eq!(map.camera.left, -500.0);
+
+    editor.undo(&mut map);
+    assert_eq!(map.camera, camera);
+    editor.redo(&mut map);
+    assert_eq!(map.camera.top, 400.0);
+    editor.undo(&mut map);
+    editor.undo(&mut map);
+    assert_eq!(map.obstacles.len(), obstacles);
+    editor.redo(&mut map);
+
+    editor.tool = EditTool::Erase;
+    assert!(editor.edit(&mut map, vec2(0.0, 0.0), vec2(30.0, 0.0)));
+    assert_eq!(map.obstacles.len(), obstacles);
+    assert!(editor.redo.is_empty());
+
+    // Editing a generated map forgets its seed, undoing brings it back
+    let mut map = crate::mapgen::generate(3);
+    editor.tool = EditTool::Tree;
+    assert!(editor.edit(&mut map, vec2(0.0, 0.0), vec2(0.0, 0.0)));
+    assert_eq!(map.seed, None);
+    editor.undo(&mut map);
+    assert_eq!(map.seed, Some(3));
+}
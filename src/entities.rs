@@ -1,35 +1,55 @@
+use crate::archetypes::WolfKind;
+use crate::fences::{Fence, FenceBudget, FenceRules};
+use crate::gates::{door_open, Gate};
 use crate::gfx::{Action, MouseProj};
+use crate::guard::{Guard, Patrol, PenAura};
+use crate::maps::{Area, Map, PenKind};
+use crate::nav::{NavGrid, NavPath};
+use crate::pack::{Pack, PackOrder, FLANK_RADIUS};
+use crate::panic::PanicSpread;
+use crate::physics::Airborne;
+use crate::population::Egg;
+use crate::scoring::{Score, ScoreEvent, ScoreRule, ScoringRules, MERGE_POINTS, MULTI_MERGE_BONUS};
+use crate::steering::{arrive, separation, Steering, Velocity};
+use crate::terrain::TerrainMap;
+use crate::tools::{Called, Tool, ToolState};
+use crate::upgrades::Upgrades;
+use crate::waves::WaveDirector;
 use crate::{Children, Entity, GameState, Inputs, Parent, Query, Time, Vec2, Vec3, Without};
 use bevy::audio::prelude::*;
 use bevy::audio::AudioSink;
-use bevy::math::{vec2, vec3, Rect, Vec3Swizzles};
+use bevy::math::{vec2, vec3, Vec3Swizzles};
 use bevy::prelude::*;
 use bevy_spatial::{KDTreeAccess2D, SpatialAccess};
 use std::collections::HashSet;
 
-const HAND_SIZE: f32 = 80.0;
-
-#[derive(Default)]
-pub struct Score {
-    pub score: i32,
-    pub time_end: f64,
-}
-
-impl Score {
-    pub fn new(start: f64) -> Score {
-        Score {
-            score: 0,
-            time_end: start + 100.0,
-        }
-    }
-}
+pub(crate) const HAND_SIZE: f32 = 80.0;
+const CATCH_DISTANCE: f32 = 25.0;
+/// Carried animals are lost once their wolf is this close to the back of the forest
+const CARRY_LOST_DEPTH: f32 = 215.0;
+const AMBUSH_DISTANCE: f32 = 250.0;
+/// Speed gained per second, the same for everyone as with the old scalar speed
+const MAX_ACCEL: f32 = 100.0;
+const ANIMAL_TURN_RATE: f32 = 8.0;
+const WOLF_TURN_RATE: f32 = 5.0;
+/// How strongly wandering chickens are drawn to flower patches, 1 is as much as wandering randomly
+const FLOWER_PULL: f32 = 1.0;
 
 #[derive(Component, Default)]
 pub struct Wolf {
-    tired_until: f64,
-    scared_until: f64,
+    pub(crate) kind: WolfKind,
+    pub(crate) tired_until: f64,
+    pub(crate) scared_until: f64,
+    pub(crate) carrying: Option<Entity>,
+    chasing_since: Option<f64>,
+    clicks: u32,
+    last_click: f64,
 }
 
+/// Put on a dog or chicken caught by a wolf, it is dragged along until dropped or lost in the forest
+#[derive(Component)]
+pub struct CarriedBy(pub Entity);
+
 #[derive(Component)]
 pub struct Chicken;
 
@@ -42,9 +62,9 @@ pub struct DogChick;
 #[derive(Component)]
 pub struct TrackedByKDTree;
 
-type NNTree = KDTreeAccess2D<TrackedByKDTree>; // type alias for later
+pub(crate) type NNTree = KDTreeAccess2D<TrackedByKDTree>; // type alias for later
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Eq, PartialEq)]
 pub enum LookerLocation {
     Inside,
     Outside,
@@ -62,8 +82,8 @@ pub enum LookerState {
 pub struct Looker {
     spawn_point: Vec2,
     spawn_door: Vec2,
-    state: LookerState,
-    location: LookerLocation,
+    pub(crate) state: LookerState,
+    pub(crate) location: LookerLocation,
 }
 
 #[derive(Default, Component)]
@@ -74,44 +94,14 @@ pub struct CollisionAvoid {
 #[derive(Component)]
 pub struct Wander {
     randobjective: Option<Vec2>,
-    confined_within: Rect<f32>,
+    confined_within: Area,
 }
 
-#[derive(Component)]
-pub struct AiResult {
-    target_speed: f32,
-    target_dir: Vec2,
-}
-
-#[derive(Component)]
-pub struct Speed(pub f32);
-
 #[derive(Component)]
 pub struct BobAnim {
     pub anim: f32,
 }
 
-const OUTSIDE: Rect<f32> = Rect {
-    left: -1000.0,
-    right: 1000.0,
-    top: 1000.0,
-    bottom: -650.0,
-};
-
-const DOGCHICK_ENCLOT: Rect<f32> = Rect {
-    left: -217.0,
-    right: 217.0,
-    top: -720.0,
-    bottom: -1000.0,
-};
-
-const FOREST: Rect<f32> = Rect {
-    left: -780.0,
-    right: 780.0,
-    top: 615.0,
-    bottom: 140.0,
-};
-
 #[derive(Component, Default)]
 pub struct DogChickAnim {
     t: f32,
@@ -126,13 +116,15 @@ pub struct DespawnIn {
 
 // Write a system that changes the state to gameend when the game is over
 pub fn game_over_system(
-    score: Res<Score>,
+    mut score: ResMut<Score>,
+    rules: Res<ScoringRules>,
     mut state: ResMut<GameState>,
     time: Res<Time>,
     audio: Res<Audio>,
     asset_server: Res<AssetServer>,
 ) {
     if matches!(*state, GameState::Playing) && time.seconds_since_startup() > score.time_end {
+        score.finish_round(&rules);
         audio.play(asset_server.load("tada.ogg"));
         *state = GameState::EndGame {
             score_sent: false,
@@ -179,6 +171,8 @@ pub fn despawnin(
 }
 
 // Add 100 points when any dogs and chickens merge together to the Score resource
+// Merges chained within the combo window multiply the points, and merges completing on the same
+// frame get an additional bonus each.
 // Spawn a text floating above the added dogchick that says the number of points added using a brown color
 pub fn score_merge(
     mut commands: Commands,
@@ -192,16 +186,28 @@ pub fn score_merge(
         return;
     }
 
+    let n_merged = qry.iter().count() as i32;
+
     for ent in qry.iter() {
-        score.score += 100;
+        score.combo.register(time.seconds_since_startup());
+        let mult = score.combo.multiplier();
+        let points = MERGE_POINTS * mult + MULTI_MERGE_BONUS * (n_merged - 1);
+        score.add(ScoreRule::Merge, points);
+
+        let text = if mult > 1 {
+            format!("+{} x{}", points, mult)
+        } else {
+            format!("+{}", points)
+        };
+
         commands
             .spawn()
             .insert_bundle(Text2dBundle {
                 text: Text::with_section(
-                    format!("+100"),
+                    text,
                     TextStyle {
                         font: asset_server.load("Roboto-Bold.ttf"),
-                        font_size: 30.0,
+                        font_size: 30.0 + 4.0 * (mult - 1) as f32,
                         color: Color::rgb(0.8, 0.6, 0.3),
                     },
                     TextAlignment {
@@ -222,8 +228,8 @@ pub fn score_merge(
 
 #[derive(Default)]
 pub struct SoundState {
-    new_scared_chicken: bool,
-    new_scared_dog: bool,
+    pub(crate) new_scared_chicken: bool,
+    pub(crate) new_scared_dog: bool,
 
     clear_chick: Option<Handle<AudioSink>>,
     clear_dog: Option<Handle<AudioSink>>,
@@ -234,6 +240,7 @@ pub struct SoundState {
 
 pub fn sound_update(
     hand: Res<MouseProj>,
+    tools: Res<ToolState>,
     mut state: ResMut<SoundState>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
@@ -275,6 +282,12 @@ pub fn sound_update(
         state.clear_dog = Some(sh);
         state.new_scared_dog = false;
     }
+    if tools.current != Tool::Attract {
+        state.hand_state_chick.clear();
+        state.hand_state_dog.clear();
+        return;
+    }
+
     let mut already = false;
 
     let mut newset = HashSet::new();
@@ -333,6 +346,7 @@ pub fn dogchickanim_update(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     time: Res<Time>,
+    map: Res<Map>,
     mut qry: Query<(Entity, &mut Transform, &mut DogChickAnim)>,
 ) {
     for (ent, mut trans, mut anim) in qry.iter_mut() {
@@ -347,7 +361,7 @@ pub fn dogchickanim_update(
         anim.t += time.delta_seconds();
 
         if anim.t >= 1.0 {
-            spawn_dogchick(&mut commands, &asset_server, trans.translation);
+            spawn_dogchick(&mut commands, &asset_server, &map, trans.translation);
             commands.entity(ent).despawn_recursive();
         }
     }
@@ -359,16 +373,24 @@ pub fn collision_avoidance(
     tree: Res<NNTree>,
     audio: Res<Audio>,
     mut soundstate: ResMut<SoundState>,
+    mut score_events: EventWriter<ScoreEvent>,
     asset_server: Res<AssetServer>,
     mut toavoid: Query<
         (Entity, &mut CollisionAvoid, &Transform),
         Or<(With<Wolf>, With<Dog>, With<Chicken>, With<DogChick>)>,
     >,
-    mut lookers: Query<(Entity, &Transform), Or<(With<Dog>, With<Chicken>)>>,
-    wolved: Query<&Transform, With<Wolf>>,
+    lookers: Query<
+        (Entity, &Transform),
+        (
+            Without<CarriedBy>,
+            Without<Airborne>,
+            Or<(With<Dog>, With<Chicken>)>,
+        ),
+    >,
+    wolved: Query<(&Transform, &Wolf)>,
     isdog: Query<&Dog>,
     ischick: Query<&Chicken>,
-    mut islooker: Query<&mut Looker>,
+    mut islooker: Query<&mut Looker, Without<Patrol>>,
     transqry: Query<&Transform>,
     childs: Query<&Children>,
 ) {
@@ -389,10 +411,11 @@ pub fn collision_avoidance(
     }
 
     let mut merged = vec![];
-    for (e, trans) in lookers.iter_mut() {
+    for (e, trans) in lookers.iter() {
         for (pos, e2) in tree.within_distance(trans.translation, 20.0) {
             if !merged.contains(&e)
                 && !merged.contains(&e2)
+                && lookers.contains(e2)
                 && (isdog.contains(e) && ischick.contains(e2)
                     || isdog.contains(e2) && ischick.contains(e))
             {
@@ -417,6 +440,9 @@ pub fn collision_avoidance(
                     .remove::<Looker>()
                     .remove::<Dog>()
                     .remove::<Chicken>()
+                    .remove::<Velocity>()
+                    .remove::<Steering>()
+                    .remove::<NavPath>()
                     .insert(trans.with_translation(trans.translation - dogchickpos));
                 commands
                     .entity(e2)
@@ -424,6 +450,9 @@ pub fn collision_avoidance(
                     .remove::<Looker>()
                     .remove::<Dog>()
                     .remove::<Chicken>()
+                    .remove::<Velocity>()
+                    .remove::<Steering>()
+                    .remove::<NavPath>()
                     .insert(
                         transqry
                             .get(e2)
@@ -434,8 +463,8 @@ pub fn collision_avoidance(
         }
     }
 
-    for trans in wolved.iter() {
-        for (_, e) in tree.within_distance(trans.translation, 150.0) {
+    for (trans, wolf) in wolved.iter() {
+        for (_, e) in tree.within_distance(trans.translation, wolf.kind.stats().scare_radius) {
             if let Ok(mut l) = islooker.get_mut(e) {
                 use LookerState::*;
                 match l.state {
@@ -449,24 +478,18 @@ pub fn collision_avoidance(
                         } else {
                             soundstate.new_scared_dog = true;
                         }
-
-                        let y = if ischick.contains(e) { 30.0 } else { 23.0 };
-                        let x = if ischick.contains(e) { -20.0 } else { -20.0 };
-
-                        commands
-                            .spawn()
-                            .insert(DespawnIn {
-                                until: time.seconds_since_startup() + 1.0,
-                                scale: None,
-                            })
-                            .insert(Parent(childs.get(e).unwrap()[0]))
-                            .insert_bundle(SpriteBundle {
-                                transform: Transform::default()
-                                    .with_translation(Vec3::new(x, y, 0.0))
-                                    .with_scale(vec3(0.32, 0.32, 0.0)),
-                                texture: asset_server.load("scared.png"),
-                                ..Default::default()
-                            });
+                        score_events.send(ScoreEvent::Scared {
+                            chicken: ischick.contains(e),
+                        });
+
+                        spawn_scared_bubble(
+                            &mut commands,
+                            &asset_server,
+                            time.seconds_since_startup(),
+                            childs.get(e).unwrap()[0],
+                            ischick.contains(e),
+                        );
+                        commands.entity(e).insert(PanicSpread::new(&time, 0));
                     }
                     HappyInside => {
                         l.state = ScaredInside {
@@ -481,32 +504,60 @@ pub fn collision_avoidance(
     }
 }
 
-fn spawn_dogchick(commands: &mut Commands, asset_server: &Res<AssetServer>, pos: Vec3) {
-    let x = DOGCHICK_ENCLOT.left + fastrand::f32() * (DOGCHICK_ENCLOT.right - DOGCHICK_ENCLOT.left);
-    let y =
-        DOGCHICK_ENCLOT.bottom + fastrand::f32() * (DOGCHICK_ENCLOT.top - DOGCHICK_ENCLOT.bottom);
+#[derive(Component)]
+pub struct ScaredBubble;
 
-    let door = (fastrand::f32() - 0.5) * 100.0;
+/// The exclamation mark popping above an animal that just got scared
+pub(crate) fn spawn_scared_bubble(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    now: f64,
+    sprite: Entity,
+    chicken: bool,
+) {
+    let y = if chicken { 30.0 } else { 23.0 };
+    let x = -20.0;
 
-    let sp = vec2(x, y);
-    let dogchick = commands
+    commands
         .spawn()
-        .insert(AiResult {
-            target_speed: 10.0,
-            target_dir: vec2(0.0, 0.0),
+        .insert(DespawnIn {
+            until: now + 1.0,
+            scale: None,
         })
+        .insert(Parent(sprite))
+        .insert(ScaredBubble)
+        .insert_bundle(SpriteBundle {
+            transform: Transform::default()
+                .with_translation(Vec3::new(x, y, 0.0))
+                .with_scale(vec3(0.32, 0.32, 0.0)),
+            texture: asset_server.load("scared.png"),
+            ..Default::default()
+        });
+}
+
+fn spawn_dogchick(commands: &mut Commands, asset_server: &Res<AssetServer>, map: &Map, pos: Vec3) {
+    let (enclot, door) = match map.pen(PenKind::DogChick) {
+        Some(pen) => (pen.interior(), pen.door_spot()),
+        None => (map.meadow, map.door(PenKind::DogChick)),
+    };
+
+    let sp = enclot.random_point();
+    let dogchick = commands
+        .spawn()
         .insert(Looker {
             spawn_point: sp,
-            spawn_door: vec2(door, -650.0),
+            spawn_door: door,
             state: LookerState::HappyInside,
             location: LookerLocation::Outside,
         })
         .insert(CollisionAvoid::default())
         .insert(Wander {
             randobjective: Some(sp),
-            confined_within: DOGCHICK_ENCLOT,
+            confined_within: enclot,
         })
-        .insert(Speed(0.0))
+        .insert(Velocity::default())
+        .insert(Steering::new(MAX_ACCEL, ANIMAL_TURN_RATE))
+        .insert(NavPath::default())
         .insert(TrackedByKDTree)
         .insert(DogChick)
         .insert_bundle(SpriteBundle {
@@ -531,93 +582,241 @@ fn spawn_dogchick(commands: &mut Commands, asset_server: &Res<AssetServer>, pos:
 }
 
 pub fn wolf_ai(
+    mut commands: Commands,
     time: Res<Time>,
+    aura: Res<PenAura>,
+    nav: Res<NavGrid>,
+    map: Res<Map>,
+    terrain: Res<TerrainMap>,
+    mut soundstate: ResMut<SoundState>,
     mut qry: Query<(
-        &mut Transform,
+        Entity,
+        &Transform,
         &mut Wolf,
+        &PackOrder,
         &Wander,
         &CollisionAvoid,
-        &mut AiResult,
-        &mut Speed,
+        &Velocity,
+        &mut Steering,
+        &mut NavPath,
     )>,
-    mut targets: Query<(&Transform, &Looker), (Without<Wolf>, Or<(With<Dog>, With<Chicken>)>)>,
+    targets: Query<(&Transform, &Looker), (Without<Wolf>, Without<CarriedBy>, Without<Airborne>)>,
+    ischick: Query<&Chicken>,
 ) {
-    for (mut trans, mut wolf, wander, avoid, mut res, mut speed) in qry.iter_mut() {
+    let forest = map.spawns.wolves;
+    let mut caught = vec![];
+    for (ent, trans, mut wolf, order, wander, avoid, vel, mut steer, mut path) in qry.iter_mut() {
         let mut max_speed = 40.0_f32;
         let pos = trans.translation.xy();
 
-        let mut nearest = None;
-        let mut neares_dist = f32::INFINITY;
-
-        for (trans, look) in targets.iter_mut() {
-            if matches!(look.location, LookerLocation::Inside) {
-                continue;
+        let stats = wolf.kind.stats();
+        let now = time.seconds_since_startup();
+        let is_tired = wolf.tired_until > now;
+        let is_scared = wolf.scared_until > now;
+
+        // Chickens hiding in tall grass are lost from sight
+        let prey_pos = |prey: Entity| match targets.get(prey) {
+            Ok((t, look))
+                if matches!(look.location, LookerLocation::Outside)
+                    && !caught.contains(&prey)
+                    && !(ischick.contains(prey) && terrain.hides(t.translation.xy(), pos)) =>
+            {
+                Some(t.translation.xy())
             }
+            _ => None,
+        };
 
-            let tpos = trans.translation.xy();
-            let d = tpos.distance_squared(pos);
+        let (prey, chase) = match *order {
+            _ if is_tired || is_scared || wolf.carrying.is_some() => (None, None),
+            PackOrder::Hunt { prey, approach } => match prey_pos(prey) {
+                // Go around the prey first, then charge once at the flanking position
+                Some(x) if x.distance(pos) < FLANK_RADIUS + 10.0 => (Some(prey), Some(x)),
+                Some(_) if approach.distance(pos) < 40.0 => (Some(prey), prey_pos(prey)),
+                Some(_) => (None, Some(approach)),
+                None => (None, None),
+            },
+            PackOrder::Ambush { prey, post } => match prey_pos(prey) {
+                Some(x) if x.distance(pos) < AMBUSH_DISTANCE => (Some(prey), Some(x)),
+                Some(_) => (None, Some(post)),
+                None => (None, None),
+            },
+            // Raided animals are inside their pen, so `prey_pos` would skip them
+            PackOrder::Raid { door, prey } => match prey
+                .filter(|e| !caught.contains(e))
+                .and_then(|e| Some((e, targets.get(e).ok()?.0.translation.xy())))
+            {
+                Some((e, x)) => (Some(e), Some(x)),
+                None => (None, Some(door)),
+            },
+            PackOrder::Roam | PackOrder::Retreat => (None, None),
+        };
 
-            if d < neares_dist {
-                neares_dist = d;
-                nearest = Some(tpos);
+        let objective = match (prey, chase) {
+            _ if wolf.carrying.is_some() => {
+                max_speed = stats.carry_speed;
+                vec2(pos.x.clamp(forest.left, forest.right), forest.top)
             }
-        }
-
-        let is_tired = wolf.tired_until > time.seconds_since_startup();
-        let is_scared = wolf.scared_until > time.seconds_since_startup();
-
-        let objective = match nearest {
-            Some(x) if x.distance(pos) < 600.0 && !is_tired && !is_scared => {
-                max_speed = 100.0;
+            _ if aura.repels(pos) => {
+                max_speed = stats.roam_speed;
+                pos + (pos - aura.center).normalize_or_zero() * 100.0
+            }
+            (Some(e), Some(x)) if x.distance(pos) < CATCH_DISTANCE => {
+                caught.push(e);
+                wolf.carrying = Some(e);
+                commands
+                    .entity(e)
+                    .insert(CarriedBy(ent))
+                    .remove::<TrackedByKDTree>();
+                if ischick.contains(e) {
+                    soundstate.new_scared_chicken = true;
+                } else {
+                    soundstate.new_scared_dog = true;
+                }
+                x
+            }
+            (_, Some(x)) => {
+                max_speed = stats.hunt_speed;
                 x
             }
+            _ if matches!(order, PackOrder::Retreat)
+                && !is_scared
+                && pos.y < forest.bottom + 100.0 =>
+            {
+                max_speed = 110.0;
+                vec2(
+                    pos.x.clamp(forest.left, forest.right),
+                    forest.bottom + 150.0,
+                )
+            }
             _ => {
                 let obj = wander.randobjective.unwrap_or(pos);
                 let dist2 = obj.distance_squared(pos);
                 if dist2 > 200.0 * 200.0 {
-                    max_speed = 60.0;
+                    max_speed = stats.roam_speed;
                 }
                 if is_scared && dist2 > 20.0 {
-                    max_speed = 180.0;
+                    max_speed = stats.flee_speed;
                 }
                 obj
             }
         };
 
-        if trans.translation.y < -530.0 {
-            wolf.tired_until = time.seconds_since_startup() + 15.0;
+        if trans.translation.y < map.meadow.bottom + 120.0
+            && !matches!(order, PackOrder::Raid { .. })
+        {
+            wolf.tired_until = now + 15.0;
         }
 
-        let to_obj = objective - pos;
+        if chase.is_some() && wolf.carrying.is_none() {
+            let since = *wolf.chasing_since.get_or_insert(now);
+            if now - since > stats.stamina {
+                wolf.tired_until = now + 8.0;
+                wolf.chasing_since = None;
+            }
+        } else {
+            wolf.chasing_since = None;
+        }
 
-        res.target_speed = max_speed.min(0.3 * to_obj.length_squared());
-        res.target_dir = to_obj.normalize_or_zero();
+        let objective = path.next_waypoint(&nav, pos, objective, now);
+        let max_speed = max_speed * terrain.speed(pos);
+        steer.force = arrive(pos, vel.0, objective, max_speed) + separation(avoid.getaway);
+    }
+}
 
-        let off = speed.0
-            * time.delta_seconds()
-            * (res.target_dir + vec2(fastrand::f32() * 0.1, 0.1 * fastrand::f32()) + avoid.getaway);
-        trans.translation.x += off.x;
-        trans.translation.y += off.y;
+/// Sends a penned DogChick out to patrol the meadow and chase wolves away
+pub fn release_dogchick(commands: &mut Commands, map: &Map, ent: Entity) {
+    let door = map.door_spot(PenKind::DogChick);
+    commands
+        .entity(ent)
+        .insert(Looker {
+            spawn_point: door,
+            spawn_door: door,
+            state: LookerState::Happy,
+            location: LookerLocation::Inside,
+        })
+        .insert(Wander {
+            randobjective: None,
+            confined_within: map.meadow,
+        })
+        .insert(Guard::default())
+        .insert(Patrol);
+}
+
+// Drag caught animals along with their wolf, they are lost once deep enough in the forest
+// An animal whose wolf let go (because it was clicked) runs back home scared
+pub fn wolf_carry(
+    mut commands: Commands,
+    time: Res<Time>,
+    map: Res<Map>,
+    mut score_events: EventWriter<ScoreEvent>,
+    mut wolves: Query<(&Transform, &mut Wolf)>,
+    mut carried: Query<
+        (
+            Entity,
+            &CarriedBy,
+            &mut Transform,
+            &mut Looker,
+            Option<&Chicken>,
+        ),
+        Without<Wolf>,
+    >,
+) {
+    for (ent, carrier, mut trans, mut looker, chicken) in carried.iter_mut() {
+        let (wolf_trans, mut wolf) = match wolves.get_mut(carrier.0) {
+            Ok(x) if x.1.carrying == Some(ent) => x,
+            _ => {
+                trans.rotation = Quat::IDENTITY;
+                looker.state = LookerState::Scared {
+                    until: time.seconds_since_startup() + 10.0,
+                };
+                commands
+                    .entity(ent)
+                    .remove::<CarriedBy>()
+                    .insert(TrackedByKDTree);
+                continue;
+            }
+        };
 
-        speed.0 += (res.target_speed - speed.0).min(100.0 * time.delta_seconds());
+        let wpos = wolf_trans.translation;
+        trans.translation = vec3(wpos.x + 15.0, wpos.y + 5.0, wpos.z + 0.01);
+        if wpos.y > map.meadow.bottom {
+            looker.location = LookerLocation::Outside;
+        }
+        trans.rotation =
+            Quat::from_rotation_z((time.seconds_since_startup() * 20.0).sin() as f32 * 0.3);
+
+        if wpos.y > map.spawns.wolves.top - CARRY_LOST_DEPTH {
+            wolf.carrying = None;
+            wolf.tired_until = time.seconds_since_startup() + 15.0;
+            score_events.send(ScoreEvent::AnimalLost {
+                chicken: chicken.is_some(),
+            });
+            commands.entity(ent).despawn_recursive();
+        }
     }
 }
 
-pub fn wander_update(mut qry: Query<(&Transform, &mut Wander)>) {
-    for (trans, mut wander) in qry.iter_mut() {
+// Chickens wander towards the flower patches around them
+pub fn wander_update(
+    terrain: Res<TerrainMap>,
+    mut qry: Query<(&Transform, &mut Wander, Option<&Chicken>)>,
+) {
+    for (trans, mut wander, chicken) in qry.iter_mut() {
         let pos = trans.translation.xy();
         if wander.randobjective.is_none()
             || wander.randobjective.unwrap().distance(pos) < 5.0
             || wander.randobjective.unwrap().distance(pos) > 70.0
         {
-            let newpos =
-                pos + vec2(fastrand::f32() - 0.5, fastrand::f32() - 0.5).normalize() * 70.0;
-            let r = wander.confined_within;
-            if newpos.y >= r.bottom
-                && newpos.y <= r.top
-                && newpos.x >= r.left
-                && newpos.x <= r.right
-            {
+            let flowers = chicken
+                .and(terrain.flowers_near(pos))
+                .filter(|&f| wander.confined_within.contains(f));
+            // Weaker close to the middle of the patch so they linger in it
+            let pull = flowers.map_or(Vec2::ZERO, |f| {
+                ((f - pos) / 100.0).clamp_length_max(1.0) * FLOWER_PULL
+            });
+            let dir = vec2(fastrand::f32() - 0.5, fastrand::f32() - 0.5).normalize() + pull;
+            let newpos = pos + dir.normalize_or_zero() * 70.0;
+            if wander.confined_within.contains(newpos) {
                 wander.randobjective = Some(newpos);
             }
         }
@@ -626,18 +825,33 @@ pub fn wander_update(mut qry: Query<(&Transform, &mut Wander)>) {
 
 pub fn dogchick_ai(
     time: Res<Time>,
+    map: Res<Map>,
     inp: Res<MouseProj>,
-    mut qry: Query<(
-        &mut Transform,
-        &mut Looker,
-        &CollisionAvoid,
-        &Wander,
-        &mut AiResult,
-        &mut Speed,
-    )>,
+    tools: Res<ToolState>,
+    nav: Res<NavGrid>,
+    terrain: Res<TerrainMap>,
+    gates: Query<&Gate>,
+    mut qry: Query<
+        (
+            &Transform,
+            &mut Looker,
+            &CollisionAvoid,
+            &Wander,
+            &Velocity,
+            &mut Steering,
+            &mut NavPath,
+            Option<&Guard>,
+            Option<&Called>,
+        ),
+        (Without<CarriedBy>, Without<Airborne>),
+    >,
 ) {
-    for (mut trans, mut looker, avoid, wander, mut res, mut speed) in qry.iter_mut() {
+    let now = time.seconds_since_startup();
+    for (trans, mut looker, avoid, wander, vel, mut steer, mut path, guard, called) in
+        qry.iter_mut()
+    {
         let pos = trans.translation.xy();
+        let called = called.filter(|c| c.until > now && c.to.distance(pos) > 20.0);
         let mut max_speed = 50.0_f32;
 
         use LookerLocation::*;
@@ -660,21 +874,41 @@ pub fn dogchick_ai(
         }
         let objective = match (looker.location, looker.state) {
             (Inside, HappyInside) => wander.randobjective.unwrap_or(pos),
-            (Inside, Happy) | (Outside, HappyInside) => looker.spawn_door,
-            (Inside, Scared { .. } | ScaredInside { .. }) => looker.spawn_point,
-            (Outside, Scared { .. } | ScaredInside { .. }) => looker.spawn_door,
-            (Outside, Happy) if inp.0.distance(pos) < HAND_SIZE => {
+            (Inside, Happy) | (Outside, HappyInside) => nav.towards_door(pos, looker.spawn_door),
+            (Inside, Scared { .. } | ScaredInside { .. }) => {
+                path.next_waypoint(&nav, pos, looker.spawn_point, now)
+            }
+            (Outside, Scared { .. } | ScaredInside { .. }) => {
+                nav.towards_door(pos, looker.spawn_door)
+            }
+            (Outside, Happy) if called.is_some() => {
+                max_speed = 160.0;
+                called.unwrap().to
+            }
+            (Outside, Happy)
+                if inp.0.distance(pos) < HAND_SIZE && tools.current == Tool::Attract =>
+            {
                 max_speed = 150.0;
                 let mut obj = inp.0;
-                if obj.y < -630.0 {
-                    obj.y = -630.0;
-                }
+                obj.y = obj.y.max(map.meadow.bottom + 20.0);
+                obj
+            }
+            (Outside, Happy) if inp.0.distance(pos) < HAND_SIZE && tools.current == Tool::Repel => {
+                max_speed = 150.0;
+                let mut obj = pos + (pos - inp.0).normalize_or_zero() * 60.0;
+                obj.y = obj.y.max(map.meadow.bottom + 20.0);
                 obj
             }
-            (Outside, Happy) => wander.randobjective.unwrap_or(pos),
+            (Outside, Happy) => match guard.and_then(|g| g.protect) {
+                Some(chick) => {
+                    max_speed = 120.0;
+                    chick
+                }
+                None => wander.randobjective.unwrap_or(pos),
+            },
         };
 
-        if looker.spawn_door.distance(pos) < 20.0 {
+        if looker.spawn_door.distance(pos) < 20.0 && door_open(&gates, looker.spawn_door) {
             looker.location = match looker.state {
                 Happy => Outside,
                 HappyInside => Inside,
@@ -683,35 +917,29 @@ pub fn dogchick_ai(
             }
         }
 
-        let to_obj = objective - pos;
-
-        res.target_speed = max_speed.min(0.8 * to_obj.length_squared());
-        res.target_dir = to_obj.normalize_or_zero();
-
-        let off = speed.0
-            * time.delta_seconds()
-            * (res.target_dir + vec2(fastrand::f32() * 0.1, 0.1 * fastrand::f32()) + avoid.getaway);
-        trans.translation.x += off.x;
-        trans.translation.y += off.y;
-
-        speed.0 += (res.target_speed - speed.0).min(100.0 * time.delta_seconds());
+        let max_speed = max_speed * terrain.speed(pos);
+        steer.force = arrive(pos, vel.0, objective, max_speed) + separation(avoid.getaway);
     }
 }
 
 pub fn speedbob(
     time: Res<Time>,
-    mut qry: Query<(&Speed, &Children, &AiResult)>,
-    mut bobqry: Query<(&mut Transform, &mut BobAnim), Without<AiResult>>,
+    mut qry: Query<(&Velocity, &Steering, &Children, Option<&Airborne>)>,
+    mut bobqry: Query<(&mut Transform, &mut BobAnim), Without<Steering>>,
 ) {
-    for (speed, children, airesult) in qry.iter_mut() {
+    for (vel, steer, children, air) in qry.iter_mut() {
         for child in children.iter() {
             let (mut trans, mut bobanim) = match bobqry.get_mut(*child) {
                 Ok(x) => x,
                 Err(_) => continue,
             };
-            bobanim.anim += speed.0 * time.delta_seconds() * 0.3;
+            if let Some(air) = air {
+                trans.translation.y = air.height;
+                continue;
+            }
+            bobanim.anim += vel.0.length() * time.delta_seconds() * 0.3;
             trans.translation.y = bobanim.anim.cos() * 6.0;
-            trans.scale.x = if (airesult.target_dir.x > 0.0) != (trans.scale.x < 0.0) {
+            trans.scale.x = if (steer.heading.x > 0.0) != (trans.scale.x < 0.0) {
                 -trans.scale.x
             } else {
                 trans.scale.x
@@ -720,8 +948,20 @@ pub fn speedbob(
     }
 }
 
-pub type DespawnQry<'a, 'b> =
-    Query<'a, 'b, Entity, Or<(With<Dog>, With<DogChick>, With<Wolf>, With<Chicken>)>>;
+pub type DespawnQry<'a, 'b> = Query<
+    'a,
+    'b,
+    Entity,
+    Or<(
+        With<Dog>,
+        With<DogChick>,
+        With<Wolf>,
+        With<Chicken>,
+        With<Pack>,
+        With<Egg>,
+        With<Fence>,
+    )>,
+>;
 
 pub fn start_game(
     qry: DespawnQry,
@@ -729,45 +969,66 @@ pub fn start_game(
     asset_server: &Res<AssetServer>,
     time: &Res<Time>,
     score: &mut Score,
+    director: &mut WaveDirector,
+    upgrades: &mut Upgrades,
+    fences: &mut FenceBudget,
+    fence_rules: &FenceRules,
+    map: &mut Map,
 ) {
-    *score = Score::new(time.seconds_since_startup());
+    map.round_seed = fastrand::u64(..);
+    // A restart is ranked like the round before it
+    *score = Score {
+        ranked: score.ranked,
+        ..Score::new(time.seconds_since_startup())
+    };
+    *upgrades = Upgrades::default();
+    *fences = FenceBudget::new(fence_rules);
+    director.start(map.wave_script(), time.seconds_since_startup());
 
     for ent in qry.iter() {
         commands.entity(ent).despawn_recursive();
     }
 
-    for _ in 0..10 {
-        spawn_wolf(commands, asset_server);
-    }
-
     for _ in 0..90 {
-        spawn_dog(commands, asset_server);
+        spawn_dog(commands, asset_server, map);
     }
 
     for _ in 0..90 {
-        spawn_chicken(commands, asset_server);
+        spawn_chicken(commands, asset_server, map);
     }
 }
 
-pub fn spawn_wolf(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    let x = (-0.5 + fastrand::f32()) * 1000.0;
-    let y = fastrand::f32() * 200.0 + 300.0;
-
+pub fn spawn_wolf(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    map: &Map,
+    pos: Vec2,
+    kind: WolfKind,
+) -> Entity {
+    let stats = kind.stats();
     let wolf = commands
         .spawn()
-        .insert(AiResult {
-            target_speed: 10.0,
-            target_dir: vec2(0.0, 0.0),
+        .insert(Velocity::default())
+        .insert(Steering::new(MAX_ACCEL, WOLF_TURN_RATE))
+        .insert(NavPath::default())
+        .insert(Wolf {
+            kind,
+            ..Default::default()
         })
-        .insert(Speed(0.0))
-        .insert(Wolf::default())
         .insert(Wander {
             randobjective: None,
-            confined_within: FOREST,
+            confined_within: map.spawns.wolves,
         })
         .insert(CollisionAvoid::default())
         .insert_bundle(SpriteBundle {
-            transform: Transform::default().with_translation(Vec3::new(x, y, 0.22)),
+            transform: Transform::default()
+                .with_translation(pos.extend(0.22))
+                .with_scale(Vec3::new(stats.scale, stats.scale, 1.0)),
+            // Faded along with the body, or the shadow would give sneaky wolves away
+            sprite: Sprite {
+                color: Color::rgba(1.0, 1.0, 1.0, stats.tint.a()),
+                ..Default::default()
+            },
             texture: asset_server.load("shadow.png"),
             ..Default::default()
         })
@@ -780,15 +1041,48 @@ pub fn spawn_wolf(commands: &mut Commands, asset_server: &Res<AssetServer>) {
             anim: fastrand::f32() * 32.0,
         })
         .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: stats.tint,
+                ..Default::default()
+            },
             transform: Transform::default().with_scale(Vec3::new(1.0, 1.0, 1.0)),
             texture: asset_server.load("wolf.png"),
             ..Default::default()
         });
+    wolf
 }
 
-pub fn spawn_chicken(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    let x = 500.0 + (-0.5 + fastrand::f32()) * 300.0;
-    let y = fastrand::f32() * 300.0 - 1000.0;
+/// Where an animal of `kind` appearing around `pos` lives and where it waits to go in, both in the same pen
+fn home(map: &Map, kind: PenKind, pos: Vec2) -> (Vec2, Vec2) {
+    match map.home(kind, pos) {
+        Some(pen) => {
+            let interior = pen.interior();
+            let point = if interior.contains(pos) {
+                pos
+            } else {
+                interior.random_point()
+            };
+            (point, pen.door_spot())
+        }
+        None => (pos, map.door(kind)),
+    }
+}
+
+pub fn spawn_chicken(commands: &mut Commands, asset_server: &Res<AssetServer>, map: &Map) {
+    let pos = map.spawns.chickens.random_point();
+    spawn_chicken_at(commands, asset_server, map, pos);
+}
+
+/// Spawns a chicken at `pos` in the chicken pen, it will come back there when scared
+/// When `pos` is outside of every chicken pen it spawns in one of them instead
+pub fn spawn_chicken_at(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    map: &Map,
+    pos: Vec2,
+) {
+    let (pos, door) = home(map, PenKind::Chicken, pos);
+    let (x, y) = (pos.x, pos.y);
 
     let chicken = commands
         .spawn()
@@ -801,21 +1095,19 @@ pub fn spawn_chicken(commands: &mut Commands, asset_server: &Res<AssetServer>) {
         })
         .insert(Looker {
             spawn_point: vec2(x, y),
-            spawn_door: vec2(500.0 + 100.0 * (fastrand::f32() - 0.5), -650.0),
+            spawn_door: door,
             state: LookerState::Happy,
             location: LookerLocation::Inside,
         })
         .insert(CollisionAvoid::default())
         .insert(Wander {
             randobjective: None,
-            confined_within: OUTSIDE,
+            confined_within: map.meadow,
         })
         .insert(TrackedByKDTree)
-        .insert(AiResult {
-            target_speed: 10.0,
-            target_dir: vec2(0.0, 0.0),
-        })
-        .insert(Speed(0.0))
+        .insert(Velocity::default())
+        .insert(Steering::new(MAX_ACCEL, ANIMAL_TURN_RATE))
+        .insert(NavPath::default())
         .insert(Chicken)
         .id();
 
@@ -832,9 +1124,9 @@ pub fn spawn_chicken(commands: &mut Commands, asset_server: &Res<AssetServer>) {
         });
 }
 
-pub fn spawn_dog(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    let x = -500.0 + (-0.5 + fastrand::f32()) * 300.0;
-    let y = fastrand::f32() * 300.0 - 1000.0;
+pub fn spawn_dog(commands: &mut Commands, asset_server: &Res<AssetServer>, map: &Map) {
+    let (pos, door) = home(map, PenKind::Dog, map.spawns.dogs.random_point());
+    let (x, y) = (pos.x, pos.y);
 
     let dog = commands
         .spawn()
@@ -847,21 +1139,20 @@ pub fn spawn_dog(commands: &mut Commands, asset_server: &Res<AssetServer>) {
         })
         .insert(Looker {
             spawn_point: vec2(x, y),
-            spawn_door: vec2(-500.0 + 100.0 * (fastrand::f32() - 0.5), -650.0),
+            spawn_door: door,
             state: LookerState::Happy,
             location: LookerLocation::Inside,
         })
         .insert(CollisionAvoid::default())
         .insert(Wander {
             randobjective: None,
-            confined_within: OUTSIDE,
+            confined_within: map.meadow,
         })
         .insert(TrackedByKDTree)
-        .insert(AiResult {
-            target_speed: 10.0,
-            target_dir: vec2(0.0, 0.0),
-        })
-        .insert(Speed(0.0))
+        .insert(Velocity::default())
+        .insert(Steering::new(MAX_ACCEL, ANIMAL_TURN_RATE))
+        .insert(NavPath::default())
+        .insert(Guard::default())
         .insert(Dog)
         .id();
 
@@ -879,6 +1170,7 @@ pub fn spawn_dog(commands: &mut Commands, asset_server: &Res<AssetServer>) {
 }
 
 // Write a system that makes the wolf scared when the user clicks on it
+// Scaring a hunting wolf that is about to reach an animal outside is rewarded
 pub fn wolf_scared(
     inputs: Res<Inputs>,
     asset_server: Res<AssetServer>,
@@ -886,19 +1178,54 @@ pub fn wolf_scared(
     mouse_position: Res<MouseProj>,
     time: Res<Time>,
     kd: Res<NNTree>,
-    mut query: Query<&mut Wolf>,
+    rules: Res<ScoringRules>,
+    mut score_events: EventWriter<ScoreEvent>,
+    mut query: Query<(&mut Wolf, &Transform)>,
+    lookers: Query<&Looker, Without<DogChick>>,
 ) {
     if !inputs.just_pressed.contains(&Action::ClickLeft) {
         return;
     }
 
     for (_, ent) in kd.within_distance(mouse_position.0.extend(0.2), 50.0) {
-        let mut wolf = match query.get_mut(ent) {
+        let (mut wolf, trans) = match query.get_mut(ent) {
             Ok(wolf) => wolf,
             Err(_) => continue,
         };
 
+        let now = time.seconds_since_startup();
+        if now - wolf.last_click > 2.0 {
+            wolf.clicks = 0;
+        }
+        wolf.clicks += 1;
+        wolf.last_click = now;
+
+        if wolf.clicks < wolf.kind.stats().clicks_to_scare {
+            audio.play_with_settings(
+                asset_server.load("wolfwhine.ogg"),
+                PlaybackSettings {
+                    repeat: false,
+                    volume: 0.5,
+                    speed: 0.7,
+                },
+            );
+            continue;
+        }
+        wolf.clicks = 0;
+
+        if wolf.tired_until < now && wolf.scared_until < now {
+            let about_to_catch = kd
+                .within_distance(trans.translation, rules.repel_distance)
+                .into_iter()
+                .filter_map(|(_, e)| lookers.get(e).ok())
+                .any(|l| matches!(l.location, LookerLocation::Outside));
+            if about_to_catch {
+                score_events.send(ScoreEvent::WolfRepelled);
+            }
+        }
+
         wolf.scared_until = time.seconds_since_startup() + 12.0;
+        wolf.carrying = None;
         audio.play(asset_server.load("wolfwhine.ogg"));
     }
 }
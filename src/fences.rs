@@ -0,0 +1,244 @@
+use crate::colliders::{Collider, Shape, AGENT_RADIUS};
+use crate::entities::Wolf;
+use crate::gfx::{Action, Inputs, MouseProj};
+use crate::maps::Map;
+use crate::scoring::{Score, ScoreRule};
+use crate::tools::{Tool, ToolState};
+use crate::GameState;
+use bevy::math::{vec3, Vec3Swizzles};
+use bevy::prelude::*;
+
+const FENCE_WIDTH: f32 = 8.0;
+pub(crate) const FENCE_COLOR: Color = Color::rgb(0.45, 0.25, 0.1);
+
+/// Tuning of the fences the player builds on the meadow
+pub struct FenceRules {
+    /// Length of fence given for free at the start of each round
+    pub round_budget: f32,
+    /// Points paid for each unit of length once the budget is spent, 0 forbids going over it
+    pub points_per_unit: f32,
+    pub min_length: f32,
+    pub max_length: f32,
+    /// Seconds a single wolf needs to break a fence by rubbing against it
+    pub durability: f32,
+}
+
+impl Default for FenceRules {
+    fn default() -> Self {
+        FenceRules {
+            round_budget: 800.0,
+            points_per_unit: 1.0,
+            min_length: 30.0,
+            max_length: 300.0,
+            durability: 12.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FenceBudget {
+    pub left: f32,
+    drag_from: Option<Vec2>,
+}
+
+impl FenceBudget {
+    pub fn new(rules: &FenceRules) -> FenceBudget {
+        FenceBudget {
+            left: rules.round_budget,
+            drag_from: None,
+        }
+    }
+
+    /// Points needed on top of the budget to build a fence of `length`, `None` if it can't be built
+    pub fn cost(&self, rules: &FenceRules, length: f32, score: &Score) -> Option<i32> {
+        if length < rules.min_length || length > rules.max_length {
+            return None;
+        }
+        let over = length - self.left;
+        if over <= 0.0 {
+            return Some(0);
+        }
+        if rules.points_per_unit == 0.0 {
+            return None;
+        }
+        let points = (over * rules.points_per_unit).ceil() as i32;
+        (points <= score.score).then_some(points)
+    }
+}
+
+#[derive(Component)]
+pub struct Fence {
+    a: Vec2,
+    b: Vec2,
+    /// Seconds of wolf rubbing left before it breaks
+    health: f32,
+    max_health: f32,
+}
+
+#[derive(Component)]
+pub struct FencePreview;
+
+/// Position, rotation and size of the sprite of a fence going from `a` to `b`
+pub(crate) fn fence_transform(a: Vec2, b: Vec2) -> (Transform, Vec2) {
+    let d = b - a;
+    let mid = (a + b) * 0.5;
+    let trans = Transform::from_translation(vec3(mid.x, mid.y, 0.21))
+        .with_rotation(Quat::from_rotation_z(d.y.atan2(d.x)));
+    (trans, Vec2::new(d.length(), FENCE_WIDTH))
+}
+
+fn in_meadow(map: &Map, p: Vec2) -> bool {
+    let m = map.meadow;
+    p.x > m.left && p.x < m.right && p.y > m.bottom + 10.0 && p.y < m.top
+}
+
+pub fn spawn_fence_preview(mut commands: Commands) {
+    commands
+        .spawn()
+        .insert(FencePreview)
+        .insert_bundle(SpriteBundle {
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        });
+}
+
+// Drag with the fence tool to build a fence from where the click started to where it is released
+pub fn build_fences(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    inputs: Res<Inputs>,
+    hand: Res<MouseProj>,
+    tools: Res<ToolState>,
+    state: Res<GameState>,
+    map: Res<Map>,
+    rules: Res<FenceRules>,
+    mut budget: ResMut<FenceBudget>,
+    mut score: ResMut<Score>,
+    mut preview: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<FencePreview>>,
+) {
+    let building = tools.current == Tool::Fence && matches!(*state, GameState::Playing);
+    if !building {
+        budget.drag_from = None;
+    } else if inputs.just_pressed.contains(&Action::ClickLeft) && in_meadow(&map, hand.0) {
+        budget.drag_from = Some(hand.0);
+    }
+
+    let (a, b) = match budget.drag_from {
+        Some(a) => (a, hand.0),
+        None => {
+            for (_, _, mut vis) in preview.iter_mut() {
+                vis.is_visible = false;
+            }
+            return;
+        }
+    };
+    let length = a.distance(b);
+    let cost = budget
+        .cost(&rules, length, &score)
+        .filter(|_| in_meadow(&map, b));
+
+    if inputs.pressed.contains(&Action::ClickLeft) {
+        for (mut trans, mut sprite, mut vis) in preview.iter_mut() {
+            let (t, size) = fence_transform(a, b);
+            *trans = t;
+            sprite.custom_size = Some(size);
+            sprite.color = match cost {
+                Some(0) => Color::rgba(0.3, 0.9, 0.3, 0.6),
+                Some(_) => Color::rgba(0.9, 0.8, 0.3, 0.6),
+                None => Color::rgba(0.9, 0.3, 0.3, 0.6),
+            };
+            vis.is_visible = true;
+        }
+        return;
+    }
+
+    budget.drag_from = None;
+    let points = match cost {
+        Some(x) => x,
+        None => return,
+    };
+    budget.left = (budget.left - length).max(0.0);
+    if points > 0 {
+        score.add(ScoreRule::Fences, -points);
+    }
+    audio.play_with_settings(
+        asset_server.load(Tool::Fence.sound()),
+        PlaybackSettings {
+            repeat: false,
+            volume: 0.6,
+            speed: 1.0,
+        },
+    );
+
+    let (transform, size) = fence_transform(a, b);
+    commands
+        .spawn()
+        .insert(Fence {
+            a,
+            b,
+            health: rules.durability,
+            max_health: rules.durability,
+        })
+        .insert(Collider {
+            shapes: vec![Shape::Segment { a, b }],
+        })
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(size),
+                color: FENCE_COLOR,
+                ..Default::default()
+            },
+            transform,
+            ..Default::default()
+        });
+}
+
+// Wolves pushing against a fence wear it down until it breaks
+pub fn wear_fences(
+    mut commands: Commands,
+    time: Res<Time>,
+    wolves: Query<&Transform, With<Wolf>>,
+    mut fences: Query<(Entity, &mut Fence, &mut Sprite)>,
+) {
+    for (ent, mut fence, mut sprite) in fences.iter_mut() {
+        let seg = Shape::Segment {
+            a: fence.a,
+            b: fence.b,
+        };
+        let rubbing = wolves
+            .iter()
+            .filter(|t| {
+                let p = t.translation.xy();
+                p.distance(seg.closest_point(p)) < AGENT_RADIUS + 2.0
+            })
+            .count();
+        if rubbing == 0 {
+            continue;
+        }
+        fence.health -= rubbing as f32 * time.delta_seconds();
+        if fence.health <= 0.0 {
+            commands.entity(ent).despawn_recursive();
+            continue;
+        }
+        sprite
+            .color
+            .set_a(0.3 + 0.7 * fence.health / fence.max_health);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_fence_cost() {
+    let rules = FenceRules::default();
+    let mut budget = FenceBudget::new(&rules);
+    let mut score = Score::new(0.0);
+
+    assert_eq!(budget.cost(&rules, 10.0, &score), None);
+    assert_eq!(budget.cost(&rules, 200.0, &score), Some(0));
+
+    budget.left = 50.0;
+    assert_eq!(budget.cost(&rules, 200.0, &score), None);
+    score.score = 1000;
+    assert_eq!(budget.cost(&rules, 200.0, &score), Some(150));
+}
@@ -0,0 +1,113 @@
+use crate::entities::{CarriedBy, Chicken, Dog, Looker, LookerLocation, LookerState, NNTree};
+use crate::physics::Airborne;
+use crate::steering::{Steering, Velocity};
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+
+/// Boids weights of one species, applied to happy animals roaming outside
+#[derive(Copy, Clone)]
+pub struct FlockWeights {
+    /// Neighbours further than this are ignored
+    pub radius: f32,
+    /// Pull towards the center of the neighbours
+    pub cohesion: f32,
+    /// Pull towards the average velocity of the neighbours
+    pub alignment: f32,
+    /// Push away from neighbours, stronger when they are close
+    pub separation: f32,
+}
+
+/// Flocking of each species, `None` disables it
+pub struct FlockRules {
+    pub chicken: Option<FlockWeights>,
+    pub dog: Option<FlockWeights>,
+}
+
+impl Default for FlockRules {
+    fn default() -> Self {
+        FlockRules {
+            chicken: Some(FlockWeights {
+                radius: 70.0,
+                cohesion: 1.5,
+                alignment: 0.8,
+                separation: 1.0,
+            }),
+            dog: Some(FlockWeights {
+                radius: 50.0,
+                cohesion: 0.3,
+                alignment: 0.2,
+                separation: 1.5,
+            }),
+        }
+    }
+}
+
+impl FlockRules {
+    fn weights(&self, chicken: bool) -> Option<FlockWeights> {
+        if chicken {
+            self.chicken
+        } else {
+            self.dog
+        }
+    }
+}
+
+// Runs after the AI systems so the flocking forces add up to the ones they wrote
+pub fn flocking(
+    tree: Res<NNTree>,
+    rules: Res<FlockRules>,
+    agents: Query<
+        (Entity, &Transform, &Velocity, &Looker, Option<&Chicken>),
+        (
+            Or<(With<Dog>, With<Chicken>)>,
+            Without<Airborne>,
+            Without<CarriedBy>,
+        ),
+    >,
+    mut steers: Query<&mut Steering>,
+) {
+    let flocks =
+        |l: &Looker| l.location == LookerLocation::Outside && matches!(l.state, LookerState::Happy);
+
+    for (ent, trans, vel, looker, chicken) in agents.iter() {
+        let w = match rules.weights(chicken.is_some()) {
+            Some(w) if flocks(looker) => w,
+            _ => continue,
+        };
+        let pos = trans.translation.xy();
+
+        let mut n = 0;
+        let mut center = Vec2::ZERO;
+        let mut heading = Vec2::ZERO;
+        let mut away = Vec2::ZERO;
+        for (_, e) in tree.within_distance(trans.translation, w.radius) {
+            let (other_pos, other_vel) = match agents.get(e) {
+                Ok((_, t, v, l, c))
+                    if e != ent && c.is_some() == chicken.is_some() && flocks(l) =>
+                {
+                    (t.translation.xy(), v.0)
+                }
+                _ => continue,
+            };
+            let diff = pos - other_pos;
+            n += 1;
+            center += other_pos;
+            heading += other_vel;
+            away += diff / diff.length_squared().max(1.0);
+        }
+
+        if n == 0 {
+            continue;
+        }
+        center /= n as f32;
+        heading /= n as f32;
+
+        let force = (center - pos) * w.cohesion
+            + (heading - vel.0) * w.alignment
+            + away * w.separation * w.radius * 10.0;
+        if let Ok(mut steer) = steers.get_mut(ent) {
+            steer.force += force;
+        }
+    }
+}
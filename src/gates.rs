@@ -0,0 +1,119 @@
+use crate::colliders::{door_gap, Collider, Shape};
+use crate::gfx::{Action, Inputs, MouseProj};
+use crate::GameState;
+use bevy::math::{vec2, vec3};
+use bevy::prelude::*;
+
+/// How close to the middle of a gate a click must be to open or close it
+const GATE_CLICK_DISTANCE: f32 = 40.0;
+const GATE_WIDTH: f32 = 10.0;
+
+/// The gate in the door gap of a pen, animals only go in and out while it is open
+#[derive(Component)]
+pub struct Gate {
+    /// Where animals wait to go in, same as their `spawn_door`
+    pub door: Vec2,
+    hinge: Vec2,
+    latch: Vec2,
+    pub open: bool,
+    /// Seconds of wolves pushing on it since it was closed
+    pub strain: f32,
+}
+
+impl Gate {
+    pub fn set_open(&mut self, open: bool, collider: &mut Collider, trans: &mut Transform) {
+        self.open = open;
+        self.strain = 0.0;
+        *collider = self.collider();
+        *trans = self.transform();
+    }
+
+    fn collider(&self) -> Collider {
+        Collider {
+            shapes: if self.open {
+                vec![]
+            } else {
+                vec![Shape::Segment {
+                    a: self.hinge,
+                    b: self.latch,
+                }]
+            },
+        }
+    }
+
+    /// Closed the gate spans the gap, open it is swung outwards around its hinge
+    fn transform(&self) -> Transform {
+        let len = self.hinge.distance(self.latch);
+        let (mid, angle) = if self.open {
+            (
+                self.hinge + vec2(0.0, len * 0.5),
+                std::f32::consts::FRAC_PI_2,
+            )
+        } else {
+            ((self.hinge + self.latch) * 0.5, 0.0)
+        };
+        Transform::from_translation(vec3(mid.x, mid.y, 0.03))
+            .with_rotation(Quat::from_rotation_z(angle))
+    }
+}
+
+/// Whether animals can go through the door, doors without a gate are always open
+pub fn door_open(gates: &Query<&Gate>, door: Vec2) -> bool {
+    gates
+        .iter()
+        .filter(|g| g.door.distance(door) < 100.0)
+        .all(|g| g.open)
+}
+
+pub fn spawn_gate(commands: &mut Commands, pen_center: Vec2, door: Vec2) -> Entity {
+    let (hinge, latch) = door_gap(pen_center);
+    let gate = Gate {
+        door,
+        hinge,
+        latch,
+        open: true,
+        strain: 0.0,
+    };
+    commands
+        .spawn()
+        .insert(gate.collider())
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.55, 0.3, 0.1),
+                custom_size: Some(vec2(hinge.distance(latch), GATE_WIDTH)),
+                ..Default::default()
+            },
+            transform: gate.transform(),
+            ..Default::default()
+        })
+        .insert(gate)
+        .id()
+}
+
+pub fn toggle_gates(
+    inputs: Res<Inputs>,
+    hand: Res<MouseProj>,
+    state: Res<GameState>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    mut gates: Query<(&mut Gate, &mut Collider, &mut Transform)>,
+) {
+    if !inputs.just_pressed.contains(&Action::ClickLeft) || matches!(*state, GameState::Editor) {
+        return;
+    }
+    for (mut gate, mut collider, mut trans) in gates.iter_mut() {
+        if ((gate.hinge + gate.latch) * 0.5).distance(hand.0) > GATE_CLICK_DISTANCE {
+            continue;
+        }
+        let open = !gate.open;
+        gate.set_open(open, &mut collider, &mut trans);
+        audio.play_with_settings(
+            asset_server.load("merge.ogg"),
+            PlaybackSettings {
+                repeat: false,
+                volume: 0.5,
+                speed: if gate.open { 0.7 } else { 0.5 },
+            },
+        );
+    }
+}
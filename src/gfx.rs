@@ -1,3 +1,4 @@
+use crate::maps::Map;
 use bevy::input::keyboard::KeyboardInput;
 use bevy::input::mouse::{MouseButtonInput, MouseWheel};
 use bevy::input::ElementState;
@@ -8,89 +9,12 @@ use std::collections::HashSet;
 #[derive(Component)]
 pub(crate) struct Yoo;
 
-pub(crate) fn gfx_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub(crate) fn gfx_setup(mut commands: Commands) {
     let mut b = OrthographicCameraBundle::new_2d();
     b.transform.scale.x = 2.0;
     b.transform.scale.y = 2.0;
     commands.spawn_bundle(b);
 
-    commands.spawn().insert_bundle(SpriteBundle {
-        sprite: Sprite {
-            custom_size: Some(Vec2::new(5000.0, 5000.0)),
-            ..Default::default()
-        },
-        transform: Transform::default()
-            .with_scale(Vec3::new(1.0, 1.0, 1.0))
-            .with_translation(Vec3::new(0.0, 0.0, 0.00)),
-        texture: asset_server.load("fond.jpg"),
-        ..SpriteBundle::default()
-    });
-
-    commands.spawn().insert_bundle(SpriteBundle {
-        transform: Transform::default()
-            .with_scale(Vec3::new(1.0, 1.0, 1.0))
-            .with_translation(Vec3::new(-500.0, -800.0, 0.02)),
-        texture: asset_server.load("enclot.png"),
-        ..SpriteBundle::default()
-    });
-    commands.spawn().insert_bundle(SpriteBundle {
-        transform: Transform::default()
-            .with_scale(Vec3::new(1.0, 1.0, 1.0))
-            .with_translation(Vec3::new(0.0, -800.0, 0.02)),
-        texture: asset_server.load("enclot.png"),
-        ..SpriteBundle::default()
-    });
-    commands.spawn().insert_bundle(SpriteBundle {
-        transform: Transform::default()
-            .with_scale(Vec3::new(1.0, 1.0, 1.0))
-            .with_translation(Vec3::new(500.0, -800.0, 0.02)),
-        texture: asset_server.load("enclot.png"),
-        ..SpriteBundle::default()
-    });
-    commands.spawn().insert_bundle(SpriteBundle {
-        transform: Transform::default()
-            .with_scale(Vec3::new(2.0, 1.0, 1.0))
-            .with_translation(Vec3::new(0.0, 350.0, 0.001)),
-        texture: asset_server.load("forest_bg.png"),
-        ..SpriteBundle::default()
-    });
-
-    let mut already: Vec<(f32, f32)> = vec![];
-
-    for _ in 0..300 {
-        let x = (-0.5 + fastrand::f32()) * 1500.0;
-        let y = (-0.5 + 0.5 * fastrand::f32()) * 700.0;
-
-        let mut is_ok = true;
-        for (xx, yy) in &already {
-            if (xx - x).abs().hypot((yy - y).abs()) < 20.0 {
-                is_ok = false;
-                break;
-            }
-        }
-        if !is_ok {
-            continue;
-        }
-
-        already.push((x, y));
-
-        commands.spawn().insert_bundle(SpriteBundle {
-            transform: Transform::default()
-                .with_scale(Vec3::new(1.0, 1.0, 1.0))
-                .with_translation(Vec3::new(x, 550.0 + y, 0.2 - y * 0.00001)),
-            texture: asset_server.load("trunk.png"),
-            ..SpriteBundle::default()
-        });
-
-        commands.spawn().insert_bundle(SpriteBundle {
-            transform: Transform::default()
-                .with_scale(Vec3::new(1.0, 1.0, 1.0))
-                .with_translation(Vec3::new(x, 550.0 + y, 0.3 - y * 0.001)),
-            texture: asset_server.load("leaves.png"),
-            ..SpriteBundle::default()
-        });
-    }
-
     commands.insert_resource(MouseProj(Vec2::default()));
 }
 
@@ -103,11 +27,16 @@ pub enum Action {
     Zoom,
     Dezoom,
     ClickLeft,
+    ToolAttract,
+    ToolRepel,
+    ToolWhistle,
+    ToolGrab,
+    ToolFence,
 }
 
 pub struct Inputs {
     pub(crate) just_pressed: HashSet<Action>,
-    pressed: HashSet<Action>,
+    pub(crate) pressed: HashSet<Action>,
 }
 
 impl Default for Inputs {
@@ -122,6 +51,7 @@ impl Default for Inputs {
 pub(crate) fn cam_movement(
     time: Res<Time>,
     inp: Res<Inputs>,
+    map: Res<Map>,
     windows: Res<Windows>,
     mut cam: Query<&mut Transform, With<Camera>>,
 ) {
@@ -157,14 +87,15 @@ pub(crate) fn cam_movement(
         h = wsize.height() * cam.scale.x;
     }
 
-    let mut bot = -1100.0 + h * 0.5;
-    let mut top = 600.0 - h * 0.5;
+    let bounds = map.camera;
+    let mut bot = bounds.bottom + h * 0.5;
+    let mut top = bounds.top - h * 0.5;
     if top < bot {
-        top = -250.0;
-        bot = -250.0;
+        top = bounds.center().y;
+        bot = bounds.center().y;
     }
 
-    cam.translation.x = cam.translation.x.clamp(-1000.0, 1000.0);
+    cam.translation.x = cam.translation.x.clamp(bounds.left, bounds.right);
     cam.translation.y = cam.translation.y.clamp(bot, top);
 
     cam.scale.x = cam.scale.x.clamp(0.01, 2.0);
@@ -190,6 +121,11 @@ pub(crate) fn input_mapping(
             KeyCode::S => Action::CamDown,
             KeyCode::D => Action::CamRight,
             KeyCode::Q | KeyCode::A => Action::CamLeft,
+            KeyCode::Key1 => Action::ToolAttract,
+            KeyCode::Key2 => Action::ToolRepel,
+            KeyCode::Key3 => Action::ToolWhistle,
+            KeyCode::Key4 => Action::ToolGrab,
+            KeyCode::Key5 => Action::ToolFence,
             _ => continue,
         };
 
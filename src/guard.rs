@@ -0,0 +1,149 @@
+use crate::entities::{Chicken, DogChick, Looker, LookerLocation, LookerState, NNTree, Wolf};
+use crate::maps::{Map, PenKind};
+use crate::scoring::ScoreEvent;
+use bevy::math::{Vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+
+const WATCH_RADIUS: f32 = 350.0;
+const BARK_RADIUS: f32 = 180.0;
+const BARK_COOLDOWN: f64 = 4.0;
+const BARK_SCARE: f64 = 3.0;
+const IDLE_BONUS_PERIOD: f64 = 5.0;
+
+/// Put on dogs, a calm dog barks at wolves coming close and, when outside, runs to help scared chickens
+/// Patrolling DogChicks also wear it, but they go after the wolves themselves
+#[derive(Component, Default)]
+pub struct Guard {
+    next_bark: f64,
+    pub(crate) protect: Option<Vec2>,
+}
+
+pub fn dog_guard(
+    time: Res<Time>,
+    tree: Res<NNTree>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    mut dogs: Query<(&Transform, &Looker, &mut Guard, Option<&Patrol>)>,
+    mut wolves: Query<&mut Wolf>,
+    chickens: Query<&Looker, With<Chicken>>,
+) {
+    let now = time.seconds_since_startup();
+    let mut barked = false;
+
+    for (trans, looker, mut guard, patrol) in dogs.iter_mut() {
+        guard.protect = None;
+        // Calm dogs in their pen still bark at raiding wolves, but stay there
+        let penned = looker.location == LookerLocation::Inside
+            && matches!(looker.state, LookerState::HappyInside);
+        if !penned
+            && (looker.location != LookerLocation::Outside
+                || !matches!(looker.state, LookerState::Happy))
+        {
+            continue;
+        }
+
+        let pos = trans.translation.xy();
+        let mut wolf_close = false;
+        let mut neares_dist = f32::INFINITY;
+
+        for (p, e) in tree.within_distance(trans.translation, WATCH_RADIUS) {
+            let d = p.xy().distance_squared(pos);
+            if wolves.contains(e) && d < BARK_RADIUS * BARK_RADIUS {
+                wolf_close = true;
+            }
+            if penned {
+                continue;
+            }
+            if patrol.is_some() {
+                if wolves.contains(e) && d < neares_dist {
+                    neares_dist = d;
+                    guard.protect = Some(p.xy());
+                }
+            } else if let Ok(chick) = chickens.get(e) {
+                if matches!(chick.state, LookerState::Scared { .. }) && d < neares_dist {
+                    neares_dist = d;
+                    guard.protect = Some(p.xy());
+                }
+            }
+        }
+
+        if !wolf_close || guard.next_bark > now {
+            continue;
+        }
+        guard.next_bark = now + BARK_COOLDOWN;
+
+        for (_, e) in tree.within_distance(trans.translation, BARK_RADIUS) {
+            if let Ok(mut wolf) = wolves.get_mut(e) {
+                wolf.scared_until = wolf.scared_until.max(now + BARK_SCARE);
+            }
+        }
+
+        if !barked {
+            barked = true;
+            audio.play_with_settings(
+                asset_server.load("dogbark1.ogg"),
+                PlaybackSettings {
+                    repeat: false,
+                    volume: 1.0,
+                    speed: fastrand::f32() * 0.2 + 0.8,
+                },
+            );
+        }
+    }
+}
+
+const AURA_BASE: f32 = 150.0;
+const AURA_PER_DOGCHICK: f32 = 15.0;
+const AURA_MAX: f32 = 550.0;
+
+/// Area around the DogChick pen that wolves keep away from, it grows with the number of DogChicks inside
+#[derive(Default)]
+pub struct PenAura {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl PenAura {
+    pub fn repels(&self, pos: Vec2) -> bool {
+        self.radius > 0.0 && pos.distance_squared(self.center) < self.radius * self.radius
+    }
+}
+
+/// Marks a DogChick released from the pen, it roams the meadow hunting wolves instead of earning points
+#[derive(Component)]
+pub struct Patrol;
+
+pub fn update_pen_aura(
+    mut aura: ResMut<PenAura>,
+    map: Res<Map>,
+    penned: Query<(), (With<DogChick>, Without<Patrol>)>,
+) {
+    let n = penned.iter().count();
+    let pen = map.pens.iter().find(|p| p.kind == PenKind::DogChick);
+    aura.center = pen.map_or(Vec2::ZERO, |p| p.interior().center());
+    aura.radius = if n == 0 || pen.is_none() {
+        0.0
+    } else {
+        (AURA_BASE + AURA_PER_DOGCHICK * n as f32).min(AURA_MAX)
+    };
+}
+
+// DogChicks resting in their pen bring in points every few seconds
+pub fn dogchick_idle_bonus(
+    time: Res<Time>,
+    mut next_bonus: Local<f64>,
+    mut score_events: EventWriter<ScoreEvent>,
+    penned: Query<(), (With<DogChick>, Without<Patrol>)>,
+) {
+    let now = time.seconds_since_startup();
+    if now < *next_bonus {
+        return;
+    }
+    *next_bonus = now + IDLE_BONUS_PERIOD;
+
+    let count = penned.iter().count() as i32;
+    if count > 0 {
+        score_events.send(ScoreEvent::DogChicksPenned { count });
+    }
+}
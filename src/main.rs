@@ -1,10 +1,49 @@
+mod archetypes;
+mod calm;
+mod colliders;
+mod editor;
 mod entities;
+mod fences;
+mod flocking;
+mod gates;
 mod gfx;
+mod guard;
+mod mapgen;
+mod maps;
+mod nav;
+mod pack;
+mod panic;
+mod physics;
+mod population;
+mod raid;
+mod scatter;
+mod scoring;
+mod steering;
+mod terrain;
+mod tools;
+mod tuning;
 mod ui;
+mod upgrades;
+mod waves;
 
-use crate::entities::{DespawnQry, Score, SoundState, TrackedByKDTree};
+use crate::colliders::Obstacles;
+use crate::editor::MapEditor;
+use crate::entities::{DespawnQry, SoundState, TrackedByKDTree};
+use crate::fences::{FenceBudget, FenceRules};
+use crate::flocking::FlockRules;
 use crate::gfx::Inputs;
+use crate::guard::PenAura;
+use crate::maps::MapList;
+use crate::nav::NavGrid;
+use crate::panic::PanicRules;
+use crate::population::{Population, PopulationRules};
+use crate::raid::RaidRules;
+use crate::scoring::{Score, ScoreEvent, ScoringRules};
+use crate::terrain::TerrainMap;
+use crate::tools::ToolState;
 use crate::ui::GameState;
+use crate::upgrades::Upgrades;
+use crate::waves::WaveDirector;
 use bevy::audio::AudioPlugin;
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
@@ -13,6 +52,8 @@ use bevy_spatial::KDTreePlugin2D;
 fn main() {
     static UI_EARLY: &str = "ui_early";
 
+    let maps = MapList::default();
+
     App::new()
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(Inputs::default())
@@ -23,6 +64,25 @@ fn main() {
         })
         .insert_resource(SoundState::default())
         .insert_resource(Score::new(0.0))
+        .insert_resource(ScoringRules::load())
+        .insert_resource(WaveDirector::default())
+        .insert_resource(PenAura::default())
+        .insert_resource(PanicRules::default())
+        .insert_resource(RaidRules::default())
+        .insert_resource(Upgrades::default())
+        .insert_resource(PopulationRules::default())
+        .insert_resource(Population::default())
+        .insert_resource(ToolState::default())
+        .insert_resource(FlockRules::default())
+        .insert_resource(Obstacles::default())
+        .insert_resource(FenceRules::default())
+        .insert_resource(FenceBudget::default())
+        .insert_resource(NavGrid::default())
+        .insert_resource(TerrainMap::default())
+        .insert_resource(maps.maps[maps.selected].clone())
+        .insert_resource(maps)
+        .insert_resource(MapEditor::default())
+        .add_event::<ScoreEvent>()
         .add_plugins(DefaultPlugins)
         .add_plugin(AudioPlugin)
         .add_plugin(EguiPlugin)
@@ -30,22 +90,60 @@ fn main() {
         .add_startup_system(ui::set_style)
         .add_startup_system(gfx::gfx_setup)
         .add_startup_system(start_background_audio)
+        .add_startup_system(tools::spawn_tool_cursor)
+        .add_startup_system(fences::spawn_fence_preview)
+        .add_startup_system(editor::spawn_editor_preview)
         .add_stage_before(CoreStage::Update, UI_EARLY, SystemStage::single_threaded())
         .add_system_to_stage(UI_EARLY, gfx::mouse_project)
         .add_system_to_stage(UI_EARLY, gfx::cam_movement)
         .add_system_to_stage(UI_EARLY, gfx::input_mapping)
         .add_system(entities::collision_avoidance)
+        .add_system(panic::panic_contagion)
+        .add_system(calm::calm_animals)
+        .add_system(calm::hearts_update)
+        .add_system(tools::tool_select)
+        .add_system(tools::tool_use.before(entities::dogchick_ai))
+        .add_system(tools::tool_cursor_update)
+        .add_system(physics::airborne_update.after(tools::tool_use))
         .add_system(ui::ui_example)
+        .add_system(editor::editor_ui)
+        .add_system(editor::editor_tools.before(maps::build_map))
+        .add_system(editor::editor_overlay)
         .add_system(entities::sound_update)
+        .add_system(guard::dog_guard.before(entities::dogchick_ai))
+        .add_system(guard::update_pen_aura.before(entities::wolf_ai))
+        .add_system(guard::dogchick_idle_bonus)
         .add_system(entities::dogchick_ai)
         .add_system(entities::speedbob)
+        .add_system(waves::wave_director)
+        .add_system(population::lay_eggs)
+        .add_system(population::hatch_eggs)
+        .add_system(population::dog_kennel)
+        .add_system(pack::pack_ai.before(entities::wolf_ai))
         .add_system(entities::wolf_ai)
+        .add_system(flocking::flocking.after(entities::dogchick_ai))
+        .add_system(
+            steering::steering_update
+                .after(entities::wolf_ai)
+                .after(flocking::flocking),
+        )
+        .add_system(maps::build_map.before(colliders::update_obstacles))
+        .add_system(fences::build_fences.before(colliders::update_obstacles))
+        .add_system(fences::wear_fences.before(colliders::update_obstacles))
+        .add_system(gates::toggle_gates.before(colliders::update_obstacles))
+        .add_system(raid::raid_gates.before(colliders::update_obstacles))
+        .add_system(colliders::update_obstacles)
+        .add_system(nav::update_nav.after(colliders::update_obstacles))
+        .add_system(terrain::update_terrain.before(entities::wolf_ai))
+        .add_system(colliders::resolve_collisions.after(steering::steering_update))
+        .add_system(entities::wolf_carry)
         .add_system(entities::despawnin)
         .add_system(entities::wander_update)
         .add_system(entities::dogchickanim_update)
         .add_system(entities::score_merge)
         .add_system(entities::wolf_scared)
         .add_system(entities::game_over_system)
+        .add_system(scoring::apply_score_rules)
         .run();
 }
 
@@ -57,6 +155,10 @@ fn start_background_audio(asset_server: Res<AssetServer>, audio: Res<Audio>) {
     std::mem::forget(asset_server.load::<AudioSource, _>("scared_chicken.ogg"));
     std::mem::forget(asset_server.load::<AudioSource, _>("tada.ogg"));
     std::mem::forget(asset_server.load::<AudioSource, _>("wolfwhine.ogg"));
+    std::mem::forget(asset_server.load::<AudioSource, _>("calm.wav"));
+    for tool in tools::ALL_TOOLS {
+        std::mem::forget(asset_server.load::<AudioSource, _>(tool.sound()));
+    }
 
     audio.play_with_settings(
         asset_server.load("I-Knew-a-Guy.ogg"),
@@ -0,0 +1,337 @@
+use crate::colliders::{door_gap, Obstacles};
+use crate::maps::{
+    Area, Background, FlowerPatch, Forest, Map, Obstacle, Pen, PenKind, Spawns, Terrain,
+};
+use crate::nav::NavGrid;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use fastrand::Rng;
+
+/// Layouts tried before falling back to the plain one
+const MAX_TRIES: usize = 20;
+const PEN_Y: f32 = -800.0;
+/// Pens are at least this far apart so their fences don't overlap
+const PEN_SPACING: f32 = 510.0;
+/// Walking distance between the dog and chicken doors
+const MIN_PEN_GAP: f32 = 700.0;
+const MAX_PEN_GAP: f32 = 1500.0;
+/// How much closer to the forest the dogs or the chickens may live
+const MAX_EXPOSURE_GAP: f32 = 150.0;
+/// Meadow obstacles keep this far from each other
+const OBSTACLE_SPACING: f32 = 100.0;
+
+const MEADOW: Area = Area {
+    left: -1000.0,
+    right: 1000.0,
+    bottom: -650.0,
+    top: 1000.0,
+};
+const CAMERA: Area = Area {
+    left: -1000.0,
+    right: 1000.0,
+    bottom: -1100.0,
+    top: 600.0,
+};
+const WOLVES: Area = Area {
+    left: -780.0,
+    right: 780.0,
+    bottom: 140.0,
+    top: 615.0,
+};
+const BACKDROP: Area = Area {
+    left: -1024.0,
+    right: 1024.0,
+    bottom: 94.0,
+    top: 606.0,
+};
+/// Where ponds, hedges, lone trees and flowers are put
+const OBSTACLE_AREA: Area = Area {
+    left: -850.0,
+    right: 850.0,
+    bottom: -420.0,
+    top: 0.0,
+};
+
+/// Why a map can't be played
+#[derive(Debug, PartialEq)]
+pub enum MapError {
+    MissingPen(PenKind),
+    /// The door of the pen is blocked or can't be reached from the forest or another pen
+    Unreachable(PenKind),
+    /// The door is not right in front of the gap in the fence of its pen
+    MisplacedDoor(PenKind),
+    PensTooClose(f32),
+    PensTooFar(f32),
+    /// Dogs or chickens live much closer to the forest than the others
+    Unfair(f32),
+}
+
+fn range(rng: &Rng, min: f32, max: f32) -> f32 {
+    min + rng.f32() * (max - min)
+}
+
+fn point_in(rng: &Rng, area: &Area) -> Vec2 {
+    vec2(
+        range(rng, area.left, area.right),
+        range(rng, area.bottom, area.top),
+    )
+}
+
+fn pen(kind: PenKind, x: f32) -> Pen {
+    Pen::new(kind, vec2(x, PEN_Y))
+}
+
+/// Where the animals of a pen are spawned at the start of a round
+fn pen_spawn(pen: &Pen) -> Area {
+    Area {
+        left: pen.center.x - 150.0,
+        right: pen.center.x + 150.0,
+        bottom: pen.center.y - 200.0,
+        top: pen.center.y + 100.0,
+    }
+}
+
+fn base_map(seed: u64, pens: Vec<Pen>, forests: Vec<Forest>) -> Map {
+    let spawn = |kind| pens.iter().find(|p| p.kind == kind).map(pen_spawn).unwrap();
+    Map {
+        name: "Random".to_string(),
+        seed: Some(seed),
+        background: Background {
+            texture: "fond.jpg".to_string(),
+            size: 5000.0,
+        },
+        camera: CAMERA,
+        meadow: MEADOW,
+        spawns: Spawns {
+            dogs: spawn(PenKind::Dog),
+            chickens: spawn(PenKind::Chicken),
+            wolves: WOLVES,
+        },
+        pens,
+        forests,
+        obstacles: vec![],
+        flowers: vec![],
+        terrain: vec![],
+        waves: None,
+        round_seed: 0,
+    }
+}
+
+/// Splits the forest band in a few patches with clearings between them
+fn forest_patches(rng: &Rng) -> Vec<Forest> {
+    let n = rng.usize(2..=4);
+    let width = 1500.0 / n as f32;
+    (0..n)
+        .map(|i| {
+            let left = -750.0 + i as f32 * width;
+            let area = Area {
+                left: left + range(rng, 0.0, 60.0),
+                right: left + width - range(rng, 0.0, 60.0),
+                bottom: range(rng, 150.0, 260.0),
+                top: 550.0,
+            };
+            Forest {
+                area,
+                trees: 4.5,
+                bushes: range(rng, 0.2, 0.8),
+                backdrop: (i == 0).then_some(BACKDROP),
+            }
+        })
+        .collect()
+}
+
+fn meadow_obstacles(rng: &Rng) -> Vec<Obstacle> {
+    let mut obstacles: Vec<Obstacle> = vec![];
+    let mut taken: Vec<(Vec2, f32)> = vec![];
+    let mut place = |center: Vec2, radius: f32, obstacle: Obstacle| {
+        if taken
+            .iter()
+            .all(|&(c, r)| c.distance(center) > r + radius + OBSTACLE_SPACING)
+        {
+            taken.push((center, radius));
+            obstacles.push(obstacle);
+        }
+    };
+
+    for _ in 0..rng.usize(0..=2) {
+        let center = point_in(rng, &OBSTACLE_AREA);
+        let radius = range(rng, 50.0, 110.0);
+        place(center, radius, Obstacle::Pond { center, radius });
+    }
+    for _ in 0..rng.usize(1..=3) {
+        let center = point_in(rng, &OBSTACLE_AREA);
+        let half = range(rng, 75.0, 150.0);
+        let angle = range(rng, 0.0, std::f32::consts::PI);
+        let d = vec2(angle.cos(), angle.sin()) * half;
+        place(center, half, Obstacle::Hedge(center - d, center + d));
+    }
+    for _ in 0..rng.usize(0..=6) {
+        let pos = point_in(rng, &OBSTACLE_AREA);
+        place(pos, 10.0, Obstacle::Trunk(pos));
+    }
+    obstacles
+}
+
+/// A patch of the meadow between 2x`min` and 2x`max` wide
+fn patch(rng: &Rng, min: Vec2, max: Vec2) -> Area {
+    let center = point_in(rng, &OBSTACLE_AREA);
+    let half = vec2(range(rng, min.x, max.x), range(rng, min.y, max.y));
+    Area::from_corners(center - half, center + half)
+}
+
+fn flower_patches(rng: &Rng) -> Vec<FlowerPatch> {
+    (0..rng.usize(1..=3))
+        .map(|_| FlowerPatch {
+            area: patch(rng, vec2(60.0, 50.0), vec2(150.0, 120.0)),
+            density: range(rng, 8.0, 20.0),
+        })
+        .collect()
+}
+
+/// Mud and tall grass, agents walk through them so they can go anywhere
+fn terrain_patches(rng: &Rng) -> Vec<Terrain> {
+    let mut terrain = vec![];
+    for _ in 0..rng.usize(0..=2) {
+        terrain.push(Terrain::Mud(patch(
+            rng,
+            vec2(80.0, 50.0),
+            vec2(200.0, 100.0),
+        )));
+    }
+    for _ in 0..rng.usize(0..=2) {
+        terrain.push(Terrain::TallGrass(patch(
+            rng,
+            vec2(80.0, 60.0),
+            vec2(180.0, 120.0),
+        )));
+    }
+    terrain
+}
+
+fn layout(rng: &Rng, seed: u64) -> Map {
+    let mut kinds = [PenKind::Dog, PenKind::DogChick, PenKind::Chicken];
+    rng.shuffle(&mut kinds);
+    let mut x = range(rng, -760.0, -560.0);
+    let pens = kinds
+        .iter()
+        .map(|&kind| {
+            let p = pen(kind, x);
+            x += PEN_SPACING + range(rng, 0.0, 130.0);
+            p
+        })
+        .collect();
+
+    let mut map = base_map(seed, pens, forest_patches(rng));
+    map.obstacles = meadow_obstacles(rng);
+    map.flowers = flower_patches(rng);
+    map.terrain = terrain_patches(rng);
+    map
+}
+
+/// A new layout of forest patches, ponds, hedges and pens, always the same for a given seed
+pub fn generate(seed: u64) -> Map {
+    let rng = Rng::with_seed(seed);
+    for _ in 0..MAX_TRIES {
+        let map = layout(&rng, seed);
+        if validate(&map).is_ok() {
+            return map;
+        }
+    }
+    // The pens of the original meadow, with nothing in the way
+    let pens = vec![
+        pen(PenKind::Dog, -500.0),
+        pen(PenKind::DogChick, 0.0),
+        pen(PenKind::Chicken, 500.0),
+    ];
+    base_map(seed, pens, forest_patches(&rng))
+}
+
+/// Checks that every pen can be reached and that dogs and chickens get the same chances
+pub fn validate(map: &Map) -> Result<(), MapError> {
+    let obstacles = Obstacles {
+        shapes: map.shapes(),
+        version: 0,
+    };
+    let doors: Vec<Vec2> = map.pens.iter().map(|p| p.door).collect();
+    let grid = NavGrid::build(&obstacles, &doors, &map.nav_bounds());
+    let forest = map.spawns.wolves;
+    // Walking distance from the forest edge right above the door
+    let exposure = |pen: &Pen| {
+        let edge = vec2(pen.door.x.clamp(forest.left, forest.right), forest.bottom);
+        grid.distance_to_door(edge, pen.door)
+    };
+
+    for pen in &map.pens {
+        let (left, right) = door_gap(pen.center);
+        if !(left.x..=right.x).contains(&pen.door.x)
+            || !(left.y..left.y + 50.0).contains(&pen.door.y)
+        {
+            return Err(MapError::MisplacedDoor(pen.kind));
+        }
+        let reachable = grid.line_free(pen.door, pen.door)
+            && exposure(pen).is_some()
+            && grid
+                .distance_to_door(pen.interior().center(), pen.door)
+                .is_some()
+            && map
+                .pens
+                .iter()
+                .all(|other| grid.distance_to_door(other.door, pen.door).is_some());
+        if !reachable {
+            return Err(MapError::Unreachable(pen.kind));
+        }
+    }
+
+    let find = |kind| {
+        map.pens
+            .iter()
+            .find(|p| p.kind == kind)
+            .ok_or(MapError::MissingPen(kind))
+    };
+    let (dog, chicken) = (find(PenKind::Dog)?, find(PenKind::Chicken)?);
+    find(PenKind::DogChick)?;
+
+    let gap = grid.distance_to_door(chicken.door, dog.door).unwrap();
+    if gap < MIN_PEN_GAP {
+        return Err(MapError::PensTooClose(gap));
+    }
+    if gap > MAX_PEN_GAP {
+        return Err(MapError::PensTooFar(gap));
+    }
+    let unfair = (exposure(dog).unwrap() - exposure(chicken).unwrap()).abs();
+    if unfair > MAX_EXPOSURE_GAP {
+        return Err(MapError::Unfair(unfair));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn test_generate() {
+    for seed in 0..5 {
+        let map = generate(seed);
+        assert_eq!(validate(&map), Ok(()));
+        assert_eq!(map.seed, Some(seed));
+
+        let again = generate(seed);
+        assert_eq!(
+            ron::to_string(&map).unwrap(),
+            ron::to_string(&again).unwrap()
+        );
+    }
+
+    let mut map = generate(1);
+    let door = map.pens[0].door;
+    map.obstacles.push(Obstacle::Pond {
+        center: door,
+        radius: 60.0,
+    });
+    assert_eq!(validate(&map), Err(MapError::Unreachable(map.pens[0].kind)));
+
+    let mut map = generate(1);
+    map.pens[1].door.x += 150.0;
+    assert_eq!(
+        validate(&map),
+        Err(MapError::MisplacedDoor(map.pens[1].kind))
+    );
+}
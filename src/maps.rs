@@ -0,0 +1,858 @@
+use crate::colliders::{door_gap, Collider, Shape, AGENT_RADIUS};
+use crate::fences::{fence_transform, FENCE_COLOR};
+use crate::gates::spawn_gate;
+use crate::mapgen::generate;
+use crate::nav::NavDoor;
+use crate::scatter::{scatter, PointGrid};
+use crate::waves::{default_script, Wave};
+use bevy::math::{const_vec2, vec2, vec3};
+use bevy::prelude::*;
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Maps shipped with the game, in the order of the menu
+const BUILTIN_MAPS: [&str; 3] = [
+    include_str!("../assets/maps/meadow.ron"),
+    include_str!("../assets/maps/crossroads.ron"),
+    include_str!("../assets/maps/riverside.ron"),
+];
+
+/// Where the maps saved with the editor go, away from the built-in ones of `assets/maps`
+#[cfg(not(target_arch = "wasm32"))]
+const MAP_DIR: &str = "maps";
+
+/// Door of a new pen, right in front of the gap
+const DOOR_OFFSET: Vec2 = const_vec2!([0.0, 150.0]);
+const TREE_SPACING: f32 = 20.0;
+const BUSH_SPACING: f32 = 40.0;
+const FLOWER_SPACING: f32 = 12.0;
+const FLOWER_COLORS: [Color; 4] = [
+    Color::WHITE,
+    Color::rgb(1.0, 0.7, 0.8),
+    Color::rgb(0.75, 0.7, 1.0),
+    Color::rgb(1.0, 0.95, 0.6),
+];
+/// The base of a trunk is below the middle of its sprite
+const TRUNK_BASE: f32 = 25.0;
+const HEDGE_WIDTH: f32 = 18.0;
+const HEDGE_COLOR: Color = Color::rgb(0.2, 0.45, 0.15);
+/// Bridges are this wide, railings included
+pub(crate) const BRIDGE_WIDTH: f32 = 100.0;
+const GRASS_SPACING: f32 = 18.0;
+const GRASS_DENSITY: f32 = 16.0;
+const MUD_COLOR: Color = Color::rgba(0.4, 0.28, 0.15, 0.75);
+const GRASS_COLOR: Color = Color::rgba(0.25, 0.5, 0.15, 0.5);
+const WATER_COLOR: Color = Color::rgb(0.25, 0.5, 0.8);
+const BRIDGE_COLOR: Color = Color::rgb(0.6, 0.42, 0.25);
+const BANK_COLOR: Color = Color::rgb(0.3, 0.25, 0.15);
+const BANK_WIDTH: f32 = 4.0;
+
+/// Axis aligned region of the map
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Area {
+    pub left: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub top: f32,
+}
+
+impl Area {
+    pub fn from_corners(a: Vec2, b: Vec2) -> Area {
+        Area {
+            left: a.x.min(b.x),
+            right: a.x.max(b.x),
+            bottom: a.y.min(b.y),
+            top: a.y.max(b.y),
+        }
+    }
+
+    pub fn contains(&self, p: Vec2) -> bool {
+        p.x >= self.left && p.x <= self.right && p.y >= self.bottom && p.y <= self.top
+    }
+
+    pub fn center(&self) -> Vec2 {
+        vec2(self.left + self.right, self.bottom + self.top) * 0.5
+    }
+
+    pub fn size(&self) -> Vec2 {
+        vec2(self.right - self.left, self.top - self.bottom)
+    }
+
+    pub fn random_point(&self) -> Vec2 {
+        vec2(self.left, self.bottom) + vec2(fastrand::f32(), fastrand::f32()) * self.size()
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PenKind {
+    Dog,
+    Chicken,
+    DogChick,
+}
+
+/// A pen, its fences are always the ones of `enclot.png` with the gap on the top side
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pen {
+    pub kind: PenKind,
+    pub center: Vec2,
+    /// Where animals wait to go in and out, in front of the gap
+    pub door: Vec2,
+}
+
+impl Pen {
+    pub fn new(kind: PenKind, center: Vec2) -> Pen {
+        Pen {
+            kind,
+            center,
+            door: center + DOOR_OFFSET,
+        }
+    }
+
+    /// Where the animals living in the pen stay
+    pub fn interior(&self) -> Area {
+        Area {
+            left: self.center.x - 200.0,
+            right: self.center.x + 200.0,
+            bottom: self.center.y - 200.0,
+            top: self.center.y + 90.0,
+        }
+    }
+
+    /// Random spot in front of the gap, far enough from its ends for an animal to walk straight in
+    pub fn door_spot(&self) -> Vec2 {
+        let (left, right) = door_gap(self.center);
+        let width = right.x - left.x - 2.0 * AGENT_RADIUS;
+        vec2(left.x + AGENT_RADIUS + fastrand::f32() * width, self.door.y)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Forest {
+    /// Where the trunks are placed
+    pub area: Area,
+    /// Trees per 100x100 square
+    pub trees: f32,
+    /// Bushes per 100x100 square, they grow between the trees
+    #[serde(default)]
+    pub bushes: f32,
+    /// Region covered by `forest_bg.png` under the trees
+    #[serde(default)]
+    pub backdrop: Option<Area>,
+}
+
+/// Flowers growing on the meadow, agents walk over them
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowerPatch {
+    pub area: Area,
+    /// Flowers per 100x100 square
+    pub density: f32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum Obstacle {
+    /// A lone tree
+    Trunk(Vec2),
+    /// A fence that can't be broken
+    Fence(Vec2, Vec2),
+    /// A row of bushes, same as a fence for the agents
+    Hedge(Vec2, Vec2),
+    Pond {
+        center: Vec2,
+        radius: f32,
+    },
+}
+
+impl Obstacle {
+    pub fn shape(&self) -> Shape {
+        match *self {
+            Obstacle::Trunk(pos) => Collider::trunk(pos - vec2(0.0, TRUNK_BASE)).shapes[0],
+            Obstacle::Fence(a, b) | Obstacle::Hedge(a, b) => Shape::Segment { a, b },
+            Obstacle::Pond { center, radius } => Shape::Circle { center, radius },
+        }
+    }
+}
+
+/// Ground that changes how animals move or are seen
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Terrain {
+    /// Slows down every animal walking in it
+    Mud(Area),
+    /// Wolves only see the chickens in it from up close
+    TallGrass(Area),
+    /// Only crossed on its bridges, it flows along the longest side of `area`
+    /// `bridges` are the x of the bridges of a river flowing left to right, their y otherwise
+    River { area: Area, bridges: Vec<f32> },
+}
+
+impl Terrain {
+    pub fn area(&self) -> &Area {
+        match self {
+            Terrain::Mud(area) | Terrain::TallGrass(area) => area,
+            Terrain::River { area, .. } => area,
+        }
+    }
+
+    /// Whether a river flows left to right
+    fn along_x(area: &Area) -> bool {
+        area.size().x >= area.size().y
+    }
+
+    /// Adds a bridge over the river where `p` is, false for other terrains
+    pub fn add_bridge(&mut self, p: Vec2) -> bool {
+        match self {
+            Terrain::River { area, bridges } => {
+                bridges.push(if Terrain::along_x(area) { p.x } else { p.y });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Banks of a river, with railings along the bridges so nothing falls off them
+    pub fn shapes(&self) -> Vec<Shape> {
+        let (area, bridges) = match self {
+            Terrain::River { area, bridges } => (area, bridges),
+            _ => return vec![],
+        };
+        // Along the river and across it
+        let along_x = Terrain::along_x(area);
+        let at = |u: f32, v: f32| if along_x { vec2(u, v) } else { vec2(v, u) };
+        let (start, end, low, high) = if along_x {
+            (area.left, area.right, area.bottom, area.top)
+        } else {
+            (area.bottom, area.top, area.left, area.right)
+        };
+        let mut bridges = bridges.clone();
+        bridges.sort_by(f32::total_cmp);
+
+        let mut shapes = vec![
+            Shape::Segment {
+                a: at(start, low),
+                b: at(start, high),
+            },
+            Shape::Segment {
+                a: at(end, low),
+                b: at(end, high),
+            },
+        ];
+        let mut bank_from = start;
+        for bridge in bridges {
+            let (a, b) = (bridge - BRIDGE_WIDTH * 0.5, bridge + BRIDGE_WIDTH * 0.5);
+            for v in [low, high] {
+                shapes.push(Shape::Segment {
+                    a: at(bank_from, v),
+                    b: at(a.max(bank_from), v),
+                });
+            }
+            for u in [a, b] {
+                shapes.push(Shape::Segment {
+                    a: at(u, low),
+                    b: at(u, high),
+                });
+            }
+            bank_from = b;
+        }
+        for v in [low, high] {
+            shapes.push(Shape::Segment {
+                a: at(bank_from, v),
+                b: at(end.max(bank_from), v),
+            });
+        }
+        // Bridges at the very ends of the river leave empty banks
+        shapes.retain(|s| !matches!(s, Shape::Segment { a, b } if a == b));
+        shapes
+    }
+
+    /// Closest point to `p` an animal can stand on, `p` itself unless it is in the water of a river
+    pub fn dry_ground(&self, p: Vec2) -> Vec2 {
+        let (area, bridges) = match self {
+            Terrain::River { area, bridges } if area.contains(p) => (area, bridges),
+            _ => return p,
+        };
+        let along_x = Terrain::along_x(area);
+        let at = |u: f32, v: f32| if along_x { vec2(u, v) } else { vec2(v, u) };
+        let (u, v, low, high) = if along_x {
+            (p.x, p.y, area.bottom, area.top)
+        } else {
+            (p.y, p.x, area.left, area.right)
+        };
+        // Far enough from the banks and railings not to be pushed back in
+        let margin = AGENT_RADIUS + 1.0;
+        let mut spots = vec![at(u, low - margin), at(u, high + margin)];
+        for &bridge in bridges {
+            let half = BRIDGE_WIDTH * 0.5 - margin;
+            let on = u.clamp(bridge - half, bridge + half);
+            if on == u {
+                return p;
+            }
+            spots.push(at(on, v));
+        }
+        spots
+            .into_iter()
+            .min_by(|a, b| a.distance(p).total_cmp(&b.distance(p)))
+            .unwrap_or(p)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Spawns {
+    pub dogs: Area,
+    pub chickens: Area,
+    /// Forest band the wolves come from, roam in and drag their catch back to
+    pub wolves: Area,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Background {
+    pub texture: String,
+    pub size: f32,
+}
+
+/// Layout of the world, read from the RON files of `assets/maps`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Map {
+    pub name: String,
+    /// Seed of generated maps, shown to the player so the layout can be shared
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub background: Background,
+    /// Where the middle of the camera can go
+    pub camera: Area,
+    /// Where animals are outside their pen
+    pub meadow: Area,
+    pub pens: Vec<Pen>,
+    pub forests: Vec<Forest>,
+    pub spawns: Spawns,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub flowers: Vec<FlowerPatch>,
+    #[serde(default)]
+    pub terrain: Vec<Terrain>,
+    /// Wolf waves of a round on this map, sorted by time
+    #[serde(default)]
+    pub waves: Option<Vec<Wave>>,
+    /// Picked by `start_game` so the trees, bushes and flowers change every round
+    #[serde(skip)]
+    pub round_seed: u64,
+}
+
+impl Map {
+    pub fn from_ron(text: &str) -> Result<Map, ron::Error> {
+        ron::from_str(text)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::new()
+                .struct_names(true)
+                .decimal_floats(true),
+        )
+    }
+
+    /// The wave script of the map, or the default one
+    pub fn wave_script(&self) -> Vec<Wave> {
+        self.waves.clone().unwrap_or_else(default_script)
+    }
+
+    /// A random pen of `kind`, falling back to any pen when the map has none of that kind
+    pub fn pen(&self, kind: PenKind) -> Option<&Pen> {
+        let pens: Vec<&Pen> = self.pens.iter().filter(|p| p.kind == kind).collect();
+        if pens.is_empty() {
+            return self.pens.first();
+        }
+        Some(pens[fastrand::usize(..pens.len())])
+    }
+
+    /// Pen of `kind` around `pos`, or a random one when `pos` is in none of them
+    pub fn home(&self, kind: PenKind, pos: Vec2) -> Option<&Pen> {
+        self.pens
+            .iter()
+            .find(|p| p.kind == kind && p.interior().contains(pos))
+            .or_else(|| self.pen(kind))
+    }
+
+    /// Door of a random pen of `kind`, animals of that kind go home through it
+    pub fn door(&self, kind: PenKind) -> Vec2 {
+        match self.pen(kind) {
+            Some(pen) => pen.door,
+            None => vec2(self.meadow.center().x, self.meadow.bottom),
+        }
+    }
+
+    /// Where an animal of `kind` waits to go home, in front of the door of a random pen of that kind
+    pub fn door_spot(&self, kind: PenKind) -> Vec2 {
+        match self.pen(kind) {
+            Some(pen) => pen.door_spot(),
+            None => self.door(kind),
+        }
+    }
+
+    /// Where an animal coming down at `p` lands, on a bank or a bridge when `p` is in a river
+    pub fn dry_ground(&self, p: Vec2) -> Vec2 {
+        self.terrain.iter().fold(p, |p, t| t.dry_ground(p))
+    }
+
+    /// Static shapes of the pens, obstacles and rivers, the trees of the forests are left out
+    pub fn shapes(&self) -> Vec<Shape> {
+        let pens = self
+            .pens
+            .iter()
+            .flat_map(|p| Collider::pen(p.center).shapes);
+        pens.chain(self.obstacles.iter().map(|o| o.shape()))
+            .chain(self.terrain.iter().flat_map(|t| t.shapes()))
+            .collect()
+    }
+
+    /// Region covered by the navigation grid, everywhere an agent can be
+    pub fn nav_bounds(&self) -> Area {
+        let (m, c) = (self.meadow, self.camera);
+        Area {
+            left: m.left.min(c.left),
+            right: m.right.max(c.right),
+            bottom: m.bottom.min(c.bottom),
+            top: m.top.max(c.top),
+        }
+    }
+}
+
+/// The maps the player can pick in the menu
+pub struct MapList {
+    pub maps: Vec<Map>,
+    pub selected: usize,
+    /// A new map is generated for every round instead of the selected one
+    pub random: bool,
+    /// Seed typed by the player, a new one is drawn each round when empty
+    pub seed_input: String,
+}
+
+impl MapList {
+    /// Only the maps shipped with the game, without the ones saved by the player
+    pub fn builtin() -> MapList {
+        MapList {
+            maps: BUILTIN_MAPS
+                .iter()
+                .map(|text| Map::from_ron(text).expect("built-in maps are valid"))
+                .collect(),
+            selected: 0,
+            random: false,
+            seed_input: String::new(),
+        }
+    }
+
+    /// Whether `map` is one of the maps shipped with the game
+    pub fn is_builtin(&self, map: &Map) -> bool {
+        self.maps[..BUILTIN_MAPS.len()]
+            .iter()
+            .any(|m| m.name == map.name)
+    }
+
+    /// Generates the map of the next round when playing on random maps
+    pub fn next_round(&self, map: &mut Map) {
+        if !self.random {
+            return;
+        }
+        let seed = self
+            .seed_input
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| fastrand::u64(..));
+        *map = generate(seed);
+    }
+
+    /// Adds `map` to the list, or replaces the one with the same name, and writes it to its file
+    /// The built-in maps can't be overwritten
+    pub fn save(&mut self, map: &Map) -> Result<String, String> {
+        if self.is_builtin(map) {
+            return Err(format!("{} is a built-in map, pick another name", map.name));
+        }
+        match self.maps.iter().position(|m| m.name == map.name) {
+            Some(i) => self.maps[i] = map.clone(),
+            None => self.maps.push(map.clone()),
+        }
+        self.selected = self.maps.iter().position(|m| m.name == map.name).unwrap();
+        self.random = false;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let text = map.to_ron().map_err(|e| e.to_string())?;
+            let file: String = map
+                .name
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let path = format!("{}/{}.ron", MAP_DIR, file);
+            std::fs::create_dir_all(MAP_DIR).map_err(|e| e.to_string())?;
+            std::fs::write(&path, text).map_err(|e| e.to_string())?;
+            Ok(format!("Saved to {}", path))
+        }
+        #[cfg(target_arch = "wasm32")]
+        Ok("Saved until the page is closed".to_string())
+    }
+}
+
+/// Maps saved with the editor, the ones named like a built-in map are skipped
+#[cfg(not(target_arch = "wasm32"))]
+fn load_saved_maps(maps: &mut Vec<Map>) {
+    let mut paths: Vec<_> = match std::fs::read_dir(MAP_DIR) {
+        Ok(dir) => dir.filter_map(|e| Some(e.ok()?.path())).collect(),
+        Err(_) => return,
+    };
+    paths.sort();
+    for path in paths {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("ron") {
+            continue;
+        }
+        let map = match std::fs::read_to_string(&path).map(|text| Map::from_ron(&text)) {
+            Ok(Ok(map)) => map,
+            Ok(Err(e)) => {
+                warn!("invalid map {}: {}", path.display(), e);
+                continue;
+            }
+            Err(e) => {
+                warn!("could not read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if maps.iter().all(|m| m.name != map.name) {
+            maps.push(map);
+        }
+    }
+}
+
+impl Default for MapList {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut list = MapList::builtin();
+        #[cfg(not(target_arch = "wasm32"))]
+        load_saved_maps(&mut list.maps);
+        list
+    }
+}
+
+/// Everything spawned from the map, despawned when another map is picked
+#[derive(Component)]
+pub struct MapEntity;
+
+fn spawn_tree(commands: &mut Commands, asset_server: &Res<AssetServer>, pos: Vec2) {
+    commands
+        .spawn()
+        .insert_bundle(SpriteBundle {
+            transform: Transform::from_translation(vec3(pos.x, pos.y, 0.2 - pos.y * 0.00001)),
+            texture: asset_server.load("trunk.png"),
+            ..SpriteBundle::default()
+        })
+        .insert(Collider::trunk(pos - vec2(0.0, TRUNK_BASE)))
+        .insert(MapEntity);
+
+    commands
+        .spawn()
+        .insert_bundle(SpriteBundle {
+            transform: Transform::from_translation(vec3(pos.x, pos.y, 0.5 - pos.y * 0.0001)),
+            texture: asset_server.load("leaves.png"),
+            ..SpriteBundle::default()
+        })
+        .insert(MapEntity);
+}
+
+fn spawn_forest(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    rng: &Rng,
+    forest: &Forest,
+) {
+    if let Some(backdrop) = forest.backdrop {
+        let center = backdrop.center();
+        commands
+            .spawn()
+            .insert_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(backdrop.size()),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(vec3(center.x, center.y, 0.001)),
+                texture: asset_server.load("forest_bg.png"),
+                ..SpriteBundle::default()
+            })
+            .insert(MapEntity);
+    }
+
+    let trees = scatter(rng, &forest.area, TREE_SPACING, forest.trees, |_| true);
+    let mut grid = PointGrid::new(&forest.area, TREE_SPACING);
+    for &tree in &trees {
+        grid.insert(tree);
+    }
+    let clear = |p: Vec2| !grid.any_within(p, TREE_SPACING);
+    for pos in scatter(rng, &forest.area, BUSH_SPACING, forest.bushes, clear) {
+        commands
+            .spawn()
+            .insert_bundle(SpriteBundle {
+                transform: Transform::from_translation(vec3(pos.x, pos.y, 0.2 - pos.y * 0.00001)),
+                texture: asset_server.load("bush.png"),
+                ..SpriteBundle::default()
+            })
+            .insert(MapEntity);
+    }
+    for pos in trees {
+        spawn_tree(commands, asset_server, pos);
+    }
+}
+
+fn spawn_flowers(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    rng: &Rng,
+    map: &Map,
+    patch: &FlowerPatch,
+) {
+    let shapes = map.shapes();
+    let clear = |p: Vec2| {
+        shapes
+            .iter()
+            .all(|s| s.push_out(p, FLOWER_SPACING).is_none())
+    };
+    for pos in scatter(rng, &patch.area, FLOWER_SPACING, patch.density, clear) {
+        commands
+            .spawn()
+            .insert_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: FLOWER_COLORS[rng.usize(..FLOWER_COLORS.len())],
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(pos.extend(0.015)),
+                texture: asset_server.load("flower.png"),
+                ..SpriteBundle::default()
+            })
+            .insert(MapEntity);
+    }
+}
+
+fn spawn_obstacle(commands: &mut Commands, asset_server: &Res<AssetServer>, obstacle: &Obstacle) {
+    let (sprite, transform, texture) = match *obstacle {
+        Obstacle::Trunk(pos) => return spawn_tree(commands, asset_server, pos),
+        Obstacle::Fence(a, b) => {
+            let (transform, size) = fence_transform(a, b);
+            let sprite = Sprite {
+                custom_size: Some(size),
+                color: FENCE_COLOR,
+                ..Default::default()
+            };
+            (sprite, transform, Default::default())
+        }
+        Obstacle::Hedge(a, b) => {
+            let (transform, size) = fence_transform(a, b);
+            let sprite = Sprite {
+                custom_size: Some(vec2(size.x + HEDGE_WIDTH, HEDGE_WIDTH)),
+                color: HEDGE_COLOR,
+                ..Default::default()
+            };
+            (sprite, transform, Default::default())
+        }
+        Obstacle::Pond { center, radius } => {
+            let sprite = Sprite {
+                custom_size: Some(Vec2::splat(radius * 2.0)),
+                ..Default::default()
+            };
+            let transform = Transform::from_translation(center.extend(0.01));
+            (sprite, transform, asset_server.load("pond.png"))
+        }
+    };
+    commands
+        .spawn()
+        .insert(Collider {
+            shapes: vec![obstacle.shape()],
+        })
+        .insert_bundle(SpriteBundle {
+            sprite,
+            transform,
+            texture,
+            ..Default::default()
+        })
+        .insert(MapEntity);
+}
+
+fn spawn_rect(commands: &mut Commands, area: &Area, color: Color, z: f32) {
+    commands
+        .spawn()
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(area.size()),
+                color,
+                ..Default::default()
+            },
+            transform: Transform::from_translation(area.center().extend(z)),
+            ..Default::default()
+        })
+        .insert(MapEntity);
+}
+
+fn spawn_terrain(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    rng: &Rng,
+    terrain: &Terrain,
+) {
+    match terrain {
+        Terrain::Mud(area) => spawn_rect(commands, area, MUD_COLOR, 0.012),
+        Terrain::TallGrass(area) => {
+            spawn_rect(commands, area, GRASS_COLOR, 0.012);
+            // Above the animals so the ones inside are half hidden
+            for pos in scatter(rng, area, GRASS_SPACING, GRASS_DENSITY, |_| true) {
+                commands
+                    .spawn()
+                    .insert_bundle(SpriteBundle {
+                        transform: Transform::from_translation(pos.extend(0.25)),
+                        texture: asset_server.load("grass.png"),
+                        ..SpriteBundle::default()
+                    })
+                    .insert(MapEntity);
+            }
+        }
+        Terrain::River { area, bridges } => {
+            spawn_rect(commands, area, WATER_COLOR, 0.012);
+            for &bridge in bridges {
+                let deck = if Terrain::along_x(area) {
+                    Area {
+                        left: bridge - BRIDGE_WIDTH * 0.5,
+                        right: bridge + BRIDGE_WIDTH * 0.5,
+                        ..*area
+                    }
+                } else {
+                    Area {
+                        bottom: bridge - BRIDGE_WIDTH * 0.5,
+                        top: bridge + BRIDGE_WIDTH * 0.5,
+                        ..*area
+                    }
+                };
+                spawn_rect(commands, &deck, BRIDGE_COLOR, 0.013);
+            }
+            for shape in terrain.shapes() {
+                if let Shape::Segment { a, b } = shape {
+                    let (transform, size) = fence_transform(a, b);
+                    commands
+                        .spawn()
+                        .insert(Collider {
+                            shapes: vec![shape],
+                        })
+                        .insert_bundle(SpriteBundle {
+                            sprite: Sprite {
+                                custom_size: Some(vec2(size.x, BANK_WIDTH)),
+                                color: BANK_COLOR,
+                                ..Default::default()
+                            },
+                            transform,
+                            ..Default::default()
+                        })
+                        .insert(MapEntity);
+                }
+            }
+        }
+    }
+}
+
+// Builds the world again whenever another map is picked
+pub fn build_map(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    map: Res<Map>,
+    old: Query<Entity, With<MapEntity>>,
+) {
+    if !map.is_changed() {
+        return;
+    }
+    for ent in old.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+
+    commands
+        .spawn()
+        .insert_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(map.background.size)),
+                ..Default::default()
+            },
+            texture: asset_server.load(map.background.texture.as_str()),
+            ..SpriteBundle::default()
+        })
+        .insert(MapEntity);
+
+    for pen in &map.pens {
+        commands
+            .spawn()
+            .insert_bundle(SpriteBundle {
+                transform: Transform::from_translation(pen.center.extend(0.02)),
+                texture: asset_server.load("enclot.png"),
+                ..SpriteBundle::default()
+            })
+            .insert(Collider::pen(pen.center))
+            .insert(NavDoor(pen.door))
+            .insert(MapEntity);
+        let gate = spawn_gate(&mut commands, pen.center, pen.door);
+        commands.entity(gate).insert(MapEntity);
+    }
+
+    // Decorations only change with the round, rebuilding the map for an edit keeps them in place
+    let rng = Rng::with_seed(map.round_seed);
+    for forest in &map.forests {
+        spawn_forest(&mut commands, &asset_server, &rng, forest);
+    }
+    for patch in &map.flowers {
+        spawn_flowers(&mut commands, &asset_server, &rng, &map, patch);
+    }
+
+    for terrain in &map.terrain {
+        spawn_terrain(&mut commands, &asset_server, &rng, terrain);
+    }
+
+    for obstacle in &map.obstacles {
+        spawn_obstacle(&mut commands, &asset_server, obstacle);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_builtin_maps() {
+    let mut maps = MapList::builtin();
+    assert_eq!(maps.maps.len(), BUILTIN_MAPS.len());
+    let meadow = maps.maps[0].clone();
+    assert!(maps.save(&meadow).is_err());
+    for map in &maps.maps {
+        for kind in [PenKind::Dog, PenKind::Chicken, PenKind::DogChick] {
+            let pen = map.pen(kind).unwrap();
+            assert_eq!(pen.kind, kind);
+            assert!(map.meadow.contains(pen.door));
+            assert!(!map.meadow.contains(pen.interior().center()));
+            // Animals walk straight in from anywhere they wait at the door
+            let fences = Collider::pen(pen.center);
+            let spot = pen.door_spot();
+            assert!((0..=20).all(|i| {
+                let p = spot.lerp(vec2(spot.x, pen.center.y), i as f32 / 20.0);
+                fences
+                    .shapes
+                    .iter()
+                    .all(|s| s.push_out(p, AGENT_RADIUS).is_none())
+            }));
+        }
+        assert!(map.camera.contains(map.meadow.center()));
+        assert_eq!(crate::mapgen::validate(map), Ok(()));
+        let saved = Map::from_ron(&map.to_ron().unwrap()).unwrap();
+        assert_eq!(saved.pens.len(), map.pens.len());
+        assert_eq!(saved.camera, map.camera);
+        let script = map.wave_script();
+        assert!(script.windows(2).all(|w| w[0].at <= w[1].at));
+    }
+
+    // Animals of a map with two pens of their kind live in the one around them
+    let mut map = maps.maps[0].clone();
+    let other = Pen::new(PenKind::Dog, vec2(0.0, 600.0));
+    map.pens.push(other.clone());
+    let home = map.home(PenKind::Dog, other.center).unwrap();
+    assert_eq!(home.center, other.center);
+}
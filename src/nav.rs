@@ -0,0 +1,347 @@
+use crate::colliders::{Obstacles, AGENT_RADIUS};
+use crate::maps::{Area, Map};
+use bevy::math::vec2;
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+const CELL: f32 = 20.0;
+/// Cost of a straight and of a diagonal step
+const STEP: u32 = 10;
+const DIAG_STEP: u32 = 14;
+/// A* gives up after this many cells, the wolf then steers straight
+const MAX_EXPANSIONS: usize = 4000;
+/// Seconds between two path searches of the same wolf
+const REPATH_DELAY: f64 = 0.3;
+/// How far along the flow field an animal looks for a shortcut
+const LOOKAHEAD: usize = 6;
+
+const NEIGHBOURS: [(i32, i32, u32); 8] = [
+    (1, 0, STEP),
+    (-1, 0, STEP),
+    (0, 1, STEP),
+    (0, -1, STEP),
+    (1, 1, DIAG_STEP),
+    (1, -1, DIAG_STEP),
+    (-1, 1, DIAG_STEP),
+    (-1, -1, DIAG_STEP),
+];
+
+/// Entrance of a pen, every door gets its own flow field
+#[derive(Component)]
+pub struct NavDoor(pub Vec2);
+
+struct FlowField {
+    door: Vec2,
+    /// Cost to reach the door from each cell, `u32::MAX` when it can't be reached
+    cost: Vec<u32>,
+}
+
+/// Walkable cells of the map, built from the obstacles
+/// An empty grid means nothing is in the way and everyone steers straight
+#[derive(Default)]
+pub struct NavGrid {
+    /// Bottom left corner of the first cell
+    origin: Vec2,
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+    fields: Vec<FlowField>,
+    pub version: u32,
+}
+
+type Cell = (i32, i32);
+
+impl NavGrid {
+    /// Grid covering `bounds`, agents outside of it steer straight
+    pub fn build(obstacles: &Obstacles, doors: &[Vec2], bounds: &Area) -> NavGrid {
+        let size = (bounds.size() / CELL).ceil();
+        let mut grid = NavGrid {
+            origin: vec2(bounds.left, bounds.bottom),
+            width: size.x as i32,
+            height: size.y as i32,
+            blocked: vec![false; (size.x * size.y) as usize],
+            fields: vec![],
+            version: obstacles.version,
+        };
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                let p = grid.center((x, y));
+                let i = grid.index((x, y));
+                grid.blocked[i] = obstacles
+                    .shapes
+                    .iter()
+                    .any(|s| s.push_out(p, AGENT_RADIUS).is_some());
+            }
+        }
+        grid.fields = doors
+            .iter()
+            .map(|&door| FlowField {
+                door,
+                cost: grid.flow_from(door),
+            })
+            .collect();
+        grid
+    }
+
+    fn cell_of(&self, p: Vec2) -> Option<Cell> {
+        let c = ((p - self.origin) / CELL).floor();
+        let (x, y) = (c.x as i32, c.y as i32);
+        (x >= 0 && y >= 0 && x < self.width && y < self.height).then_some((x, y))
+    }
+
+    fn center(&self, (x, y): Cell) -> Vec2 {
+        self.origin + vec2(x as f32 + 0.5, y as f32 + 0.5) * CELL
+    }
+
+    fn index(&self, (x, y): Cell) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn is_blocked(&self, c: Cell) -> bool {
+        self.blocked.get(self.index(c)).copied().unwrap_or(false)
+    }
+
+    /// Neighbours of a cell that can be walked to, diagonals can't cut a blocked corner
+    fn neighbours(&self, (x, y): Cell) -> impl Iterator<Item = (Cell, u32)> + '_ {
+        NEIGHBOURS.iter().filter_map(move |&(dx, dy, cost)| {
+            let n = (x + dx, y + dy);
+            if n.0 < 0 || n.1 < 0 || n.0 >= self.width || n.1 >= self.height || self.is_blocked(n) {
+                return None;
+            }
+            if dx != 0 && dy != 0 && (self.is_blocked((x + dx, y)) || self.is_blocked((x, y + dy)))
+            {
+                return None;
+            }
+            Some((n, cost))
+        })
+    }
+
+    /// Dijkstra from `goal` over the whole grid
+    fn flow_from(&self, goal: Vec2) -> Vec<u32> {
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let start = match self.cell_of(goal) {
+            Some(c) => c,
+            None => return cost,
+        };
+        let mut open = BinaryHeap::new();
+        cost[self.index(start)] = 0;
+        open.push(Reverse((0, start)));
+        while let Some(Reverse((c, cell))) = open.pop() {
+            if c > cost[self.index(cell)] {
+                continue;
+            }
+            for (n, step) in self.neighbours(cell) {
+                if c + step < cost[self.index(n)] {
+                    cost[self.index(n)] = c + step;
+                    open.push(Reverse((c + step, n)));
+                }
+            }
+        }
+        cost
+    }
+
+    /// Whether an agent can walk straight from `a` to `b`
+    pub fn line_free(&self, a: Vec2, b: Vec2) -> bool {
+        if self.blocked.is_empty() {
+            return true;
+        }
+        let steps = (a.distance(b) / (CELL * 0.5)).ceil().max(1.0) as usize;
+        (0..=steps).all(|i| match self.cell_of(a.lerp(b, i as f32 / steps as f32)) {
+            Some(c) => !self.is_blocked(c),
+            None => true,
+        })
+    }
+
+    /// Walking distance from `p` to `door`, `None` when there is no way there
+    pub fn distance_to_door(&self, p: Vec2, door: Vec2) -> Option<f32> {
+        let field = self.fields.iter().find(|f| f.door == door)?;
+        let cost = field.cost[self.index(self.cell_of(p)?)];
+        (cost != u32::MAX).then(|| cost as f32 * CELL / STEP as f32)
+    }
+
+    /// Where an animal heading to `door` should go next, following the flow field of the closest door
+    pub fn towards_door(&self, pos: Vec2, door: Vec2) -> Vec2 {
+        if self.line_free(pos, door) {
+            return door;
+        }
+        let field = match self
+            .fields
+            .iter()
+            .filter(|f| f.door.distance(door) < 100.0)
+            .min_by(|a, b| a.door.distance(door).total_cmp(&b.door.distance(door)))
+        {
+            Some(f) => f,
+            None => return door,
+        };
+        let mut cell = match self.cell_of(pos) {
+            Some(c) => c,
+            None => return door,
+        };
+
+        let mut target = door;
+        for _ in 0..LOOKAHEAD {
+            let next = self
+                .neighbours(cell)
+                .min_by_key(|&(n, _)| field.cost[self.index(n)])
+                .filter(|&(n, _)| field.cost[self.index(n)] < field.cost[self.index(cell)]);
+            cell = match next {
+                Some((n, _)) => n,
+                None => break,
+            };
+            if target != door && !self.line_free(pos, self.center(cell)) {
+                break;
+            }
+            target = self.center(cell);
+        }
+        target
+    }
+
+    /// A* from `from` to `to`, the path is shortened to the corners the agent really has to go round
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let (start, goal) = (self.cell_of(from)?, self.cell_of(to)?);
+        let heuristic = |(x, y): Cell| {
+            let (dx, dy) = ((x - goal.0).unsigned_abs(), (y - goal.1).unsigned_abs());
+            STEP * dx.max(dy) + (DIAG_STEP - STEP) * dx.min(dy)
+        };
+
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from: Vec<Option<Cell>> = vec![None; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[self.index(start)] = 0;
+        open.push(Reverse((heuristic(start), start)));
+
+        let mut expansions = 0;
+        while let Some(Reverse((_, cell))) = open.pop() {
+            if cell == goal {
+                let mut cells = vec![cell];
+                while let Some(prev) = came_from[self.index(*cells.last()?)] {
+                    cells.push(prev);
+                }
+                cells.reverse();
+                let points = cells.into_iter().map(|c| self.center(c)).collect();
+                return Some(self.smooth(from, points, to));
+            }
+            expansions += 1;
+            if expansions > MAX_EXPANSIONS {
+                return None;
+            }
+            let c = cost[self.index(cell)];
+            for (n, step) in self.neighbours(cell) {
+                if c + step < cost[self.index(n)] {
+                    cost[self.index(n)] = c + step;
+                    came_from[self.index(n)] = Some(cell);
+                    open.push(Reverse((c + step + heuristic(n), n)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Drops the waypoints that can be skipped by walking straight
+    fn smooth(&self, from: Vec2, points: Vec<Vec2>, to: Vec2) -> Vec<Vec2> {
+        let mut path = vec![];
+        let mut last = from;
+        for (i, &p) in points.iter().enumerate() {
+            let next = points.get(i + 1).copied().unwrap_or(to);
+            if !self.line_free(last, next) {
+                path.push(p);
+                last = p;
+            }
+        }
+        path.push(to);
+        path
+    }
+}
+
+/// Path of an animal to its objective, searched again when the objective moves or the map changes
+#[derive(Component, Default)]
+pub struct NavPath {
+    goal: Option<Cell>,
+    waypoints: Vec<Vec2>,
+    version: u32,
+    next_search: f64,
+}
+
+impl NavPath {
+    /// Where the animal should steer to reach `goal`
+    pub fn next_waypoint(&mut self, grid: &NavGrid, pos: Vec2, goal: Vec2, now: f64) -> Vec2 {
+        if grid.line_free(pos, goal) {
+            self.waypoints.clear();
+            self.goal = None;
+            return goal;
+        }
+
+        let goal_cell = grid.cell_of(goal);
+        if (goal_cell != self.goal || grid.version != self.version) && now >= self.next_search {
+            self.waypoints = grid.find_path(pos, goal).unwrap_or_default();
+            self.goal = goal_cell;
+            self.version = grid.version;
+            self.next_search = now + REPATH_DELAY;
+        }
+
+        while self.waypoints.len() > 1
+            && (self.waypoints[0].distance(pos) < CELL || grid.line_free(pos, self.waypoints[1]))
+        {
+            self.waypoints.remove(0);
+        }
+        self.waypoints.first().copied().unwrap_or(goal)
+    }
+}
+
+pub fn update_nav(
+    obstacles: Res<Obstacles>,
+    map: Res<Map>,
+    mut grid: ResMut<NavGrid>,
+    doors: Query<&NavDoor>,
+    changed_doors: Query<(), Changed<NavDoor>>,
+) {
+    if grid.version == obstacles.version && changed_doors.is_empty() {
+        return;
+    }
+    let doors: Vec<Vec2> = doors.iter().map(|d| d.0).collect();
+    *grid = NavGrid::build(&obstacles, &doors, &map.nav_bounds());
+}
+
+#[cfg(test)]
+#[test]
+fn test_nav() {
+    use crate::colliders::Collider;
+
+    let obstacles = Obstacles {
+        shapes: Collider::pen(vec2(0.0, -800.0)).shapes,
+        version: 1,
+    };
+    let door = vec2(0.0, -650.0);
+    let bounds = Area {
+        left: -1000.0,
+        right: 1000.0,
+        bottom: -1100.0,
+        top: 1000.0,
+    };
+    let grid = NavGrid::build(&obstacles, &[door], &bounds);
+
+    assert!(grid.line_free(vec2(0.0, -800.0), door));
+    assert!(!grid.line_free(vec2(-150.0, -800.0), vec2(-150.0, -600.0)));
+
+    // From a corner of the pen the flow field leads along the fence to the gap
+    let mut pos = vec2(-200.0, -700.0);
+    for _ in 0..100 {
+        pos = grid.towards_door(pos, door);
+        if pos == door {
+            break;
+        }
+    }
+    assert_eq!(pos, door);
+
+    let path = grid
+        .find_path(vec2(-150.0, -800.0), vec2(-150.0, -500.0))
+        .unwrap();
+    assert_eq!(*path.last().unwrap(), vec2(-150.0, -500.0));
+    assert!(path.len() >= 2);
+    let mut last = vec2(-150.0, -800.0);
+    for &p in &path {
+        assert!(grid.line_free(last, p));
+        last = p;
+    }
+}
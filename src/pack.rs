@@ -0,0 +1,307 @@
+use crate::archetypes::WolfKind;
+use crate::entities::{spawn_wolf, CarriedBy, Chicken, Dog, Looker, LookerLocation, NNTree, Wolf};
+use crate::gates::{door_open, Gate};
+use crate::maps::{Map, PenKind};
+use crate::raid::RaidRules;
+use crate::terrain::TerrainMap;
+use crate::waves::WaveDirector;
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+use std::collections::HashMap;
+
+const SIGHT_DISTANCE: f32 = 600.0;
+const LOSE_DISTANCE: f32 = 800.0;
+const HUNT_GIVE_UP: f64 = 15.0;
+const RETREAT_DURATION: f64 = 10.0;
+/// Raiders go for animals this close to the middle of the pen
+const RAID_RADIUS: f32 = 260.0;
+/// Distance from a pen door to the middle of its pen
+const DOOR_TO_PEN: f32 = 150.0;
+pub(crate) const FLANK_RADIUS: f32 = 130.0;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum PackRole {
+    /// Picks the prey for the whole pack
+    Alpha,
+    /// Circles around the prey to come at it from another side
+    Flanker,
+    /// Waits at the forest edge and only charges when the prey comes close
+    Ambusher,
+}
+
+#[derive(Component)]
+pub struct Pack {
+    pub alpha: Entity,
+    pub target: Option<Entity>,
+    pub hunt_since: f64,
+    pub retreat_until: f64,
+    /// Last time a wolf of the pack caught something, set when the pack is first seen
+    pub last_meal: Option<f64>,
+    /// Door of the pen being raided
+    pub raid: Option<Vec2>,
+    pub raid_since: f64,
+}
+
+#[derive(Component)]
+pub struct PackMember {
+    pub pack: Entity,
+    pub role: PackRole,
+}
+
+/// What the pack expects from a wolf this frame, read by `wolf_ai`
+#[derive(Component, Copy, Clone)]
+pub enum PackOrder {
+    Roam,
+    Hunt {
+        prey: Entity,
+        approach: Vec2,
+    },
+    Ambush {
+        prey: Entity,
+        post: Vec2,
+    },
+    /// Go through the door of a pen, and for the prey inside once the gate is open
+    Raid {
+        door: Vec2,
+        prey: Option<Entity>,
+    },
+    Retreat,
+}
+
+pub fn pack_ai(
+    mut commands: Commands,
+    time: Res<Time>,
+    tree: Res<NNTree>,
+    map: Res<Map>,
+    terrain: Res<TerrainMap>,
+    rules: Res<RaidRules>,
+    mut director: ResMut<WaveDirector>,
+    gates: Query<&Gate>,
+    mut packs: Query<(Entity, &mut Pack)>,
+    mut wolves: Query<(Entity, &Transform, &Wolf, &mut PackMember, &mut PackOrder)>,
+    prey_qry: Query<
+        (&Transform, &Looker, Option<&Chicken>),
+        (Without<CarriedBy>, Or<(With<Dog>, With<Chicken>)>),
+    >,
+) {
+    let now = time.seconds_since_startup();
+    let forest = map.spawns.wolves;
+
+    let mut members: HashMap<Entity, Vec<(Entity, Vec2, PackRole)>> = HashMap::new();
+    for (ent, trans, _, member, _) in wolves.iter() {
+        members
+            .entry(member.pack)
+            .or_default()
+            .push((ent, trans.translation.xy(), member.role));
+    }
+
+    // Where the prey is if the wolf at `from` can see it
+    let is_prey = |e: Entity, from: Vec2| match prey_qry.get(e) {
+        Ok((trans, looker, chicken))
+            if matches!(looker.location, LookerLocation::Outside)
+                && !(chicken.is_some() && terrain.hides(trans.translation.xy(), from)) =>
+        {
+            Some(trans.translation.xy())
+        }
+        _ => None,
+    };
+
+    for (pack_ent, mut pack) in packs.iter_mut() {
+        let members = match members.get(&pack_ent) {
+            Some(x) => x,
+            None => {
+                commands.entity(pack_ent).despawn();
+                continue;
+            }
+        };
+
+        if !members.iter().any(|(e, _, _)| *e == pack.alpha) {
+            pack.alpha = members[0].0;
+            if let Ok((_, _, _, mut member, _)) = wolves.get_mut(pack.alpha) {
+                member.role = PackRole::Alpha;
+            }
+        }
+
+        let (alpha_pos, alpha_scared) = match wolves.get(pack.alpha) {
+            Ok((_, trans, wolf, _, _)) => (trans.translation.xy(), wolf.scared_until > now),
+            Err(_) => continue,
+        };
+
+        // Whether the prey got away, back in its pen or out of sight
+        let lost = pack.target.is_some_and(
+            |e| !matches!(is_prey(e, alpha_pos), Some(p) if p.distance(alpha_pos) < LOSE_DISTANCE),
+        );
+
+        if pack.target.is_some() && (lost || alpha_scared || now - pack.hunt_since > HUNT_GIVE_UP) {
+            pack.target = None;
+            pack.retreat_until = now + RETREAT_DURATION;
+        }
+
+        if members
+            .iter()
+            .any(|(e, _, _)| matches!(wolves.get(*e), Ok((_, _, w, _, _)) if w.carrying.is_some()))
+        {
+            pack.last_meal = Some(now);
+            pack.raid = None;
+        }
+        let hungry = now - *pack.last_meal.get_or_insert(now) > rules.hunger;
+
+        if pack.raid.is_some()
+            && (alpha_scared || pack.retreat_until > now || now - pack.raid_since > rules.give_up)
+        {
+            pack.raid = None;
+            pack.retreat_until = pack.retreat_until.max(now + RETREAT_DURATION);
+        }
+
+        let retreating = pack.retreat_until > now;
+
+        if rules.enabled
+            && hungry
+            && pack.raid.is_none()
+            && pack.target.is_none()
+            && !retreating
+            && members.len() >= rules.min_pack_size
+        {
+            // The DogChick pen has nothing to eat
+            pack.raid = map
+                .pens
+                .iter()
+                .filter(|p| matches!(p.kind, PenKind::Dog | PenKind::Chicken))
+                .map(|p| p.door)
+                .min_by(|a, b| a.distance(alpha_pos).total_cmp(&b.distance(alpha_pos)));
+            if pack.raid.is_some() {
+                pack.raid_since = now;
+                director.announcement = Some(("Wolves are raiding a pen!".to_string(), now + 3.0));
+            }
+        }
+
+        if let Some(door) = pack.raid.filter(|_| !retreating) {
+            // The gate has to be forced open before going for the animals inside
+            let mut prey = None;
+            if door_open(&gates, door) {
+                let pen = door - Vec2::Y * DOOR_TO_PEN;
+                let mut neares_dist = f32::INFINITY;
+                for (pos, e) in tree.within_distance(pen.extend(0.0), RAID_RADIUS) {
+                    let d = pos.xy().distance_squared(alpha_pos);
+                    let inside = matches!(
+                        prey_qry.get(e),
+                        Ok((_, l, _)) if l.location == LookerLocation::Inside
+                    );
+                    if inside && d < neares_dist {
+                        neares_dist = d;
+                        prey = Some(e);
+                    }
+                }
+            }
+            for &(ent, _, _) in members {
+                if let Ok((_, _, _, _, mut o)) = wolves.get_mut(ent) {
+                    *o = PackOrder::Raid { door, prey };
+                }
+            }
+            continue;
+        }
+
+        if pack.target.is_none() && !retreating {
+            let mut neares_dist = f32::INFINITY;
+            for (pos, e) in tree.within_distance(alpha_pos.extend(0.0), SIGHT_DISTANCE) {
+                let d = pos.xy().distance_squared(alpha_pos);
+                if d < neares_dist && is_prey(e, alpha_pos).is_some() {
+                    neares_dist = d;
+                    pack.target = Some(e);
+                }
+            }
+            if pack.target.is_some() {
+                pack.hunt_since = now;
+            }
+        }
+
+        let target = pack.target.and_then(|e| Some((e, is_prey(e, alpha_pos)?)));
+
+        // Hunters spread evenly on a circle around the prey, starting from the side the pack comes from
+        let hunters = members
+            .iter()
+            .filter(|(_, _, role)| *role != PackRole::Ambusher)
+            .count();
+        let center = members
+            .iter()
+            .fold(Vec2::ZERO, |acc, (_, pos, _)| acc + *pos)
+            / members.len() as f32;
+        let mut slot = 0;
+
+        for &(ent, _, role) in members {
+            let order = match target {
+                _ if retreating => PackOrder::Retreat,
+                None => PackOrder::Roam,
+                Some((prey, prey_pos)) if role == PackRole::Ambusher => PackOrder::Ambush {
+                    prey,
+                    post: vec2(prey_pos.x.clamp(forest.left, forest.right), forest.bottom),
+                },
+                Some((prey, prey_pos)) => {
+                    let base = (center - prey_pos).normalize_or_zero();
+                    let angle = slot as f32 * std::f32::consts::TAU / hunters as f32;
+                    slot += 1;
+                    let (sin, cos) = angle.sin_cos();
+                    let dir = vec2(base.x * cos - base.y * sin, base.x * sin + base.y * cos);
+                    PackOrder::Hunt {
+                        prey,
+                        approach: prey_pos + dir * FLANK_RADIUS,
+                    }
+                }
+            };
+            if let Ok((_, _, _, _, mut o)) = wolves.get_mut(ent) {
+                *o = order;
+            }
+        }
+    }
+}
+
+/// Spawns `size` wolves together around `center`, with one alpha and an ambusher for bigger packs
+/// Big packs are led by a bold wolf
+pub fn spawn_pack(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    map: &Map,
+    center: Vec2,
+    size: usize,
+) {
+    if size == 0 {
+        return;
+    }
+    let pack = commands.spawn().id();
+    let mut alpha = None;
+
+    for i in 0..size {
+        let pos = center + vec2(fastrand::f32() - 0.5, fastrand::f32() - 0.5) * 100.0;
+        let role = if i == 0 {
+            PackRole::Alpha
+        } else if size >= 4 && i == size - 1 {
+            PackRole::Ambusher
+        } else {
+            PackRole::Flanker
+        };
+        let kind = match role {
+            PackRole::Alpha if size >= 4 => WolfKind::Bold,
+            PackRole::Ambusher => WolfKind::Sneaky,
+            _ => WolfKind::random_follower(),
+        };
+        let wolf = spawn_wolf(commands, asset_server, map, pos, kind);
+        alpha.get_or_insert(wolf);
+        commands
+            .entity(wolf)
+            .insert(PackMember { pack, role })
+            .insert(PackOrder::Roam);
+    }
+
+    if let Some(alpha) = alpha {
+        commands.entity(pack).insert(Pack {
+            alpha,
+            target: None,
+            hunt_since: 0.0,
+            retreat_until: 0.0,
+            last_meal: None,
+            raid: None,
+            raid_since: 0.0,
+        });
+    }
+}
@@ -0,0 +1,113 @@
+use crate::entities::{
+    spawn_scared_bubble, Chicken, Dog, Looker, LookerState, NNTree, SoundState, HAND_SIZE,
+};
+use crate::gfx::MouseProj;
+use crate::scoring::{Score, ScoreEvent};
+use crate::GameState;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+
+/// Tuning of how panic spreads from a scared animal to its neighbours
+pub struct PanicRules {
+    pub radius: f32,
+    /// Chance of scaring a neighbour right next to the panicking animal, it decays to 0 at `radius`
+    pub chance: f32,
+    /// Seconds before a newly scared animal spreads its panic
+    pub delay: f64,
+    /// Chance multiplier for animals close to the player's hand
+    pub hand_resistance: f32,
+}
+
+impl Default for PanicRules {
+    fn default() -> Self {
+        PanicRules {
+            radius: 60.0,
+            chance: 0.35,
+            delay: 0.25,
+            hand_resistance: 0.1,
+        }
+    }
+}
+
+/// Put on an animal that just got scared, it may scare its neighbours once the delay is over
+#[derive(Component)]
+pub struct PanicSpread {
+    at: f64,
+    /// How many animals the panic went through before this one, 0 when scared by a wolf
+    chain: u32,
+}
+
+impl PanicSpread {
+    pub fn new(time: &Time, chain: u32) -> PanicSpread {
+        PanicSpread {
+            at: time.seconds_since_startup(),
+            chain,
+        }
+    }
+}
+
+pub fn panic_contagion(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<GameState>,
+    tree: Res<NNTree>,
+    rules: Res<PanicRules>,
+    hand: Res<MouseProj>,
+    asset_server: Res<AssetServer>,
+    mut score: ResMut<Score>,
+    mut soundstate: ResMut<SoundState>,
+    mut score_events: EventWriter<ScoreEvent>,
+    spreading: Query<(Entity, &Transform, &PanicSpread)>,
+    mut lookers: Query<(&mut Looker, &Children, Option<&Chicken>), Or<(With<Dog>, With<Chicken>)>>,
+) {
+    let now = time.seconds_since_startup();
+
+    for (ent, trans, spread) in spreading.iter() {
+        if spread.at + rules.delay > now {
+            continue;
+        }
+        commands.entity(ent).remove::<PanicSpread>();
+
+        for (pos, e) in tree.within_distance(trans.translation, rules.radius) {
+            let (mut looker, children, chicken) = match lookers.get_mut(e) {
+                Ok(x) if e != ent && matches!(x.0.state, LookerState::Happy) => x,
+                _ => continue,
+            };
+
+            let mut chance =
+                rules.chance * (1.0 - pos.xy().distance(trans.translation.xy()) / rules.radius);
+            if pos.xy().distance(hand.0) < HAND_SIZE {
+                chance *= rules.hand_resistance;
+            }
+            if fastrand::f32() > chance {
+                continue;
+            }
+
+            looker.state = LookerState::Scared { until: now + 10.0 };
+            if chicken.is_some() {
+                soundstate.new_scared_chicken = true;
+            } else {
+                soundstate.new_scared_dog = true;
+            }
+            score_events.send(ScoreEvent::Scared {
+                chicken: chicken.is_some(),
+            });
+            spawn_scared_bubble(
+                &mut commands,
+                &asset_server,
+                now,
+                children[0],
+                chicken.is_some(),
+            );
+
+            let chain = spread.chain + 1;
+            // The stats of the round stop with it
+            if matches!(*state, GameState::Playing) {
+                score.longest_panic_chain = score.longest_panic_chain.max(chain);
+                score.panic_spread += 1;
+            }
+            commands.entity(e).insert(PanicSpread::new(&time, chain));
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::entities::{
+    spawn_scared_bubble, CarriedBy, Chicken, Dog, Looker, LookerLocation, LookerState, NNTree,
+    SoundState,
+};
+use crate::maps::Map;
+use crate::panic::PanicSpread;
+use crate::scoring::ScoreEvent;
+use crate::tools::Grabbed;
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+
+const GRAVITY: f32 = 900.0;
+/// Height at which the player holds an animal
+pub(crate) const HOLD_HEIGHT: f32 = 30.0;
+const MAX_THROW_SPEED: f32 = 1200.0;
+/// Landing faster than this scares the animal
+const HARD_LANDING: f32 = 550.0;
+/// Under this vertical speed the animal stops bouncing
+const BOUNCE_MIN: f32 = 150.0;
+const BOUNCE_DAMPING: f32 = 0.45;
+const GROUND_FRICTION: f32 = 0.6;
+/// Landing this close to a partner pulls both together for a merge
+const LANDING_MERGE_RADIUS: f32 = 45.0;
+
+/// An animal off the ground, its transform is the position of its shadow
+#[derive(Component, Default)]
+pub struct Airborne {
+    pub vel: Vec2,
+    pub vz: f32,
+    pub height: f32,
+    bounced: bool,
+}
+
+impl Airborne {
+    pub fn held() -> Airborne {
+        Airborne {
+            height: HOLD_HEIGHT,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a kick of velocity, horizontal speed is capped to keep throws on screen
+    pub fn impulse(&mut self, vel: Vec2, vz: f32) {
+        self.vel = (self.vel + vel).clamp_length_max(MAX_THROW_SPEED);
+        self.vz += vz;
+    }
+
+    /// Moves the animal at `pos` for `dt` seconds without leaving the meadow
+    /// Returns where it is and whether it touched the ground, it never comes down in a river
+    pub fn step(&mut self, pos: Vec2, dt: f32, map: &Map) -> (Vec2, bool) {
+        let meadow = map.meadow;
+        let pos = (pos + self.vel * dt).clamp(
+            vec2(meadow.left, meadow.bottom + 20.0),
+            vec2(meadow.right, meadow.top),
+        );
+        self.vz -= GRAVITY * dt;
+        self.height += self.vz * dt;
+
+        if self.height > 0.0 {
+            return (pos, false);
+        }
+        self.height = 0.0;
+        (map.dry_ground(pos), true)
+    }
+
+    /// Upward speed given to a throw, faster throws go higher
+    pub fn throw_lift(vel: Vec2) -> f32 {
+        200.0 + 0.2 * vel.length().min(MAX_THROW_SPEED)
+    }
+}
+
+pub fn airborne_update(
+    mut commands: Commands,
+    time: Res<Time>,
+    tree: Res<NNTree>,
+    map: Res<Map>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    mut soundstate: ResMut<SoundState>,
+    mut score_events: EventWriter<ScoreEvent>,
+    mut flying: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Airborne,
+            &mut Looker,
+            Option<&Chicken>,
+            &Children,
+        ),
+        Without<Grabbed>,
+    >,
+    partners: Query<
+        (&Transform, Option<&Chicken>),
+        (
+            Or<(With<Dog>, With<Chicken>)>,
+            Without<Airborne>,
+            Without<CarriedBy>,
+        ),
+    >,
+) {
+    let dt = time.delta_seconds();
+    let now = time.seconds_since_startup();
+
+    for (ent, mut trans, mut air, mut looker, chicken, children) in flying.iter_mut() {
+        let (pos, landed) = air.step(trans.translation.xy(), dt, &map);
+        trans.translation.x = pos.x;
+        trans.translation.y = pos.y;
+        if !landed {
+            continue;
+        }
+
+        let impact = (air.vel.length_squared() + air.vz * air.vz).sqrt();
+
+        let partner = tree
+            .within_distance(trans.translation, LANDING_MERGE_RADIUS)
+            .into_iter()
+            .filter_map(|(_, e)| partners.get(e).ok())
+            .find(|(_, c)| c.is_some() != chicken.is_some())
+            .map(|(t, _)| t.translation.xy());
+
+        if let Some(partner) = partner {
+            trans.translation.x = partner.x;
+            trans.translation.y = partner.y;
+            commands.entity(ent).remove::<Airborne>();
+            continue;
+        }
+
+        if !air.bounced
+            && impact > HARD_LANDING
+            && looker.location == LookerLocation::Outside
+            && matches!(looker.state, LookerState::Happy)
+        {
+            looker.state = LookerState::Scared { until: now + 10.0 };
+            if chicken.is_some() {
+                soundstate.new_scared_chicken = true;
+            } else {
+                soundstate.new_scared_dog = true;
+            }
+            score_events.send(ScoreEvent::Scared {
+                chicken: chicken.is_some(),
+            });
+            spawn_scared_bubble(
+                &mut commands,
+                &asset_server,
+                now,
+                children[0],
+                chicken.is_some(),
+            );
+            commands.entity(ent).insert(PanicSpread::new(&time, 0));
+        }
+
+        if -air.vz > BOUNCE_MIN {
+            air.vz = -air.vz * BOUNCE_DAMPING;
+            air.vel *= GROUND_FRICTION;
+            air.bounced = true;
+            audio.play_with_settings(
+                asset_server.load("merge.ogg"),
+                PlaybackSettings {
+                    repeat: false,
+                    volume: 0.2,
+                    speed: 0.6,
+                },
+            );
+            continue;
+        }
+
+        commands.entity(ent).remove::<Airborne>();
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_throw_into_river() {
+    use crate::maps::{MapList, Terrain};
+
+    let maps = MapList::builtin();
+    let map = maps.maps.iter().find(|m| m.name == "Riverside").unwrap();
+    let (river, bridge) = map
+        .terrain
+        .iter()
+        .find_map(|t| match t {
+            Terrain::River { area, bridges } => Some((*area, bridges[0])),
+            _ => None,
+        })
+        .unwrap();
+
+    // Thrown from below the river so that it comes down in the middle of it
+    let throw = |x: f32| {
+        let from = vec2(x, river.bottom - 60.0);
+        let mut air = Airborne::default();
+        let flight = 2.0 * 300.0 / GRAVITY;
+        air.impulse(vec2(0.0, (river.center().y - from.y) / flight), 300.0);
+        let mut pos = from;
+        for _ in 0..600 {
+            let (next, landed) = air.step(pos, 1.0 / 60.0, map);
+            pos = next;
+            if landed {
+                return pos;
+            }
+        }
+        panic!("never landed");
+    };
+
+    let pos = throw(bridge + 300.0);
+    assert!(!river.contains(pos));
+    assert!(pos.y < river.bottom);
+    // The bridge is dry
+    assert!(river.contains(throw(bridge)));
+}
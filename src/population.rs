@@ -0,0 +1,137 @@
+use crate::entities::{
+    spawn_chicken_at, spawn_dog, Chicken, Dog, Looker, LookerLocation, LookerState,
+};
+use crate::maps::Map;
+use crate::GameState;
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+
+/// Tuning of how the animal population grows during a round
+pub struct PopulationRules {
+    /// Eggs laid per second by each calm chicken in the pen
+    pub egg_rate: f32,
+    pub hatch_time: f64,
+    /// Seconds between two dogs coming out of the kennel
+    pub dog_interval: f64,
+    pub max_chickens: usize,
+    pub max_dogs: usize,
+}
+
+impl Default for PopulationRules {
+    fn default() -> Self {
+        PopulationRules {
+            egg_rate: 0.05,
+            hatch_time: 8.0,
+            dog_interval: 4.0,
+            max_chickens: 120,
+            max_dogs: 120,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Population {
+    next_dog: f64,
+}
+
+#[derive(Component)]
+pub struct Egg {
+    laid_at: f64,
+}
+
+fn growing(state: &GameState) -> bool {
+    matches!(*state, GameState::Playing | GameState::EndGamePlaying)
+}
+
+pub fn lay_eggs(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<GameState>,
+    rules: Res<PopulationRules>,
+    chickens: Query<(&Transform, &Looker), With<Chicken>>,
+    eggs: Query<&Egg>,
+) {
+    if !growing(&state) {
+        return;
+    }
+
+    let mut count = chickens.iter().count() + eggs.iter().count();
+
+    for (trans, looker) in chickens.iter() {
+        if count >= rules.max_chickens {
+            return;
+        }
+        if looker.location != LookerLocation::Inside
+            || matches!(looker.state, LookerState::ScaredInside { .. })
+        {
+            continue;
+        }
+        if fastrand::f32() > rules.egg_rate * time.delta_seconds() {
+            continue;
+        }
+
+        count += 1;
+        commands
+            .spawn()
+            .insert(Egg {
+                laid_at: time.seconds_since_startup(),
+            })
+            .insert_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(0.98, 0.94, 0.82),
+                    custom_size: Some(vec2(9.0, 12.0)),
+                    ..Default::default()
+                },
+                transform: Transform::default()
+                    .with_translation(trans.translation.xy().extend(0.21) - Vec3::Y * 8.0),
+                ..Default::default()
+            });
+    }
+}
+
+// Eggs wobble for a bit before hatching into a chicken that lives in the pen
+pub fn hatch_eggs(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    state: Res<GameState>,
+    map: Res<Map>,
+    rules: Res<PopulationRules>,
+    mut eggs: Query<(Entity, &Egg, &mut Transform)>,
+) {
+    if !growing(&state) {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    for (ent, egg, mut trans) in eggs.iter_mut() {
+        let left = egg.laid_at + rules.hatch_time - now;
+        if left < 2.0 {
+            trans.rotation = Quat::from_rotation_z((now * 25.0).sin() as f32 * 0.25);
+        }
+        if left < 0.0 {
+            spawn_chicken_at(&mut commands, &asset_server, &map, trans.translation.xy());
+            commands.entity(ent).despawn_recursive();
+        }
+    }
+}
+
+pub fn dog_kennel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    state: Res<GameState>,
+    map: Res<Map>,
+    rules: Res<PopulationRules>,
+    mut population: ResMut<Population>,
+    dogs: Query<&Dog>,
+) {
+    let now = time.seconds_since_startup();
+    if !growing(&state) || now < population.next_dog {
+        return;
+    }
+    population.next_dog = now + rules.dog_interval;
+
+    if dogs.iter().count() < rules.max_dogs {
+        spawn_dog(&mut commands, &asset_server, &map);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::colliders::Collider;
+use crate::gates::Gate;
+use crate::pack::PackOrder;
+use crate::GameState;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+/// Raiding wolves this close to a closed gate push on it
+const GATE_PUSH_DISTANCE: f32 = 45.0;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+pub const ALL_DIFFICULTIES: [Difficulty; 3] =
+    [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+impl Difficulty {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+}
+
+/// Tuning of the pen raids, hungry packs go for the animals inside the pens
+pub struct RaidRules {
+    pub difficulty: Difficulty,
+    pub enabled: bool,
+    /// Smaller packs never raid
+    pub min_pack_size: usize,
+    /// Seconds a pack goes without catching anything before it raids
+    pub hunger: f64,
+    /// Seconds a single wolf needs to force a closed gate open
+    pub gate_strength: f32,
+    /// Seconds after which the pack gives up the raid and retreats
+    pub give_up: f64,
+}
+
+impl RaidRules {
+    pub fn new(difficulty: Difficulty) -> RaidRules {
+        match difficulty {
+            Difficulty::Easy => RaidRules {
+                difficulty,
+                enabled: false,
+                ..RaidRules::new(Difficulty::Normal)
+            },
+            Difficulty::Normal => RaidRules {
+                difficulty,
+                enabled: true,
+                min_pack_size: 4,
+                hunger: 30.0,
+                gate_strength: 12.0,
+                give_up: 25.0,
+            },
+            Difficulty::Hard => RaidRules {
+                difficulty,
+                enabled: true,
+                min_pack_size: 3,
+                hunger: 15.0,
+                gate_strength: 6.0,
+                give_up: 40.0,
+            },
+        }
+    }
+}
+
+impl Default for RaidRules {
+    fn default() -> Self {
+        RaidRules::new(Difficulty::Normal)
+    }
+}
+
+// Raiding wolves gathered at a closed gate push it open after a while
+pub fn raid_gates(
+    time: Res<Time>,
+    state: Res<GameState>,
+    rules: Res<RaidRules>,
+    raiders: Query<(&Transform, &PackOrder)>,
+    mut gates: Query<(&mut Gate, &mut Collider, &mut Transform), Without<PackOrder>>,
+) {
+    if !matches!(*state, GameState::Playing) {
+        return;
+    }
+    for (mut gate, mut collider, mut trans) in gates.iter_mut() {
+        if gate.open {
+            continue;
+        }
+        let pushing = raiders
+            .iter()
+            .filter(|(t, order)| {
+                matches!(order, PackOrder::Raid { door, .. } if *door == gate.door)
+                    && t.translation.xy().distance(gate.door) < GATE_PUSH_DISTANCE
+            })
+            .count();
+        gate.strain += pushing as f32 * time.delta_seconds();
+        if gate.strain >= rules.gate_strength {
+            gate.set_open(true, &mut collider, &mut trans);
+        }
+    }
+}
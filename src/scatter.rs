@@ -0,0 +1,170 @@
+use crate::maps::Area;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use fastrand::Rng;
+
+/// Points tried around each placed point before it stops growing
+const CANDIDATES: usize = 30;
+/// Random points tried in a row to find a part of the region not reached yet
+const SEED_TRIES: usize = 30;
+
+/// Points sorted into square cells, to find the ones near a position without going through all of them
+pub struct PointGrid {
+    origin: Vec2,
+    cell: f32,
+    width: i32,
+    height: i32,
+    cells: Vec<Vec<Vec2>>,
+}
+
+impl PointGrid {
+    /// Empty grid covering `bounds`, points outside of it go in the border cells
+    pub fn new(bounds: &Area, cell: f32) -> PointGrid {
+        let size = (bounds.size() / cell).ceil().max(Vec2::ONE);
+        let (width, height) = (size.x as i32, size.y as i32);
+        PointGrid {
+            origin: vec2(bounds.left, bounds.bottom),
+            cell,
+            width,
+            height,
+            cells: vec![vec![]; (width * height) as usize],
+        }
+    }
+
+    fn cell_of(&self, p: Vec2) -> (i32, i32) {
+        let c = ((p - self.origin) / self.cell).floor();
+        (
+            (c.x as i32).clamp(0, self.width - 1),
+            (c.y as i32).clamp(0, self.height - 1),
+        )
+    }
+
+    pub fn insert(&mut self, p: Vec2) {
+        let (x, y) = self.cell_of(p);
+        self.cells[(y * self.width + x) as usize].push(p);
+    }
+
+    /// Whether a point of the grid is closer than `distance` to `p`
+    pub fn any_within(&self, p: Vec2, distance: f32) -> bool {
+        let reach = (distance / self.cell).ceil() as i32;
+        let (cx, cy) = self.cell_of(p);
+        for y in (cy - reach).max(0)..=(cy + reach).min(self.height - 1) {
+            for x in (cx - reach).max(0)..=(cx + reach).min(self.width - 1) {
+                let cell = &self.cells[(y * self.width + x) as usize];
+                if cell
+                    .iter()
+                    .any(|q| q.distance_squared(p) < distance * distance)
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Poisson-disk sampling over the part of `bounds` where `inside` holds, with Bridson's grid
+/// Points are at least `spacing` apart, `density` is the number of points per 100x100 square,
+/// capped by how tightly the spacing packs them
+/// The same `rng` state always gives the same points
+pub fn scatter(
+    rng: &Rng,
+    bounds: &Area,
+    spacing: f32,
+    density: f32,
+    inside: impl Fn(Vec2) -> bool,
+) -> Vec<Vec2> {
+    let size = bounds.size();
+    if spacing <= 0.0 || density <= 0.0 || size.x <= 0.0 || size.y <= 0.0 {
+        return vec![];
+    }
+    // A cell is small enough to hold a single point
+    let cell = spacing / std::f32::consts::SQRT_2;
+    let origin = vec2(bounds.left, bounds.bottom);
+
+    let mut grid = PointGrid::new(bounds, cell);
+    let mut points: Vec<Vec2> = vec![];
+    let mut active: Vec<usize> = vec![];
+
+    let fits =
+        |p: Vec2, grid: &PointGrid| bounds.contains(p) && inside(p) && !grid.any_within(p, spacing);
+
+    let mut misses = 0;
+    while misses < SEED_TRIES {
+        let p = if !active.is_empty() {
+            let k = rng.usize(..active.len());
+            let around = (0..CANDIDATES).find_map(|_| {
+                let angle = rng.f32() * std::f32::consts::TAU;
+                let dist = spacing * (1.0 + rng.f32());
+                let q = points[active[k]] + vec2(angle.cos(), angle.sin()) * dist;
+                fits(q, &grid).then_some(q)
+            });
+            if around.is_none() {
+                active.swap_remove(k);
+            }
+            around
+        } else {
+            let q = origin + vec2(rng.f32(), rng.f32()) * size;
+            let fit = fits(q, &grid);
+            misses = if fit { 0 } else { misses + 1 };
+            fit.then_some(q)
+        };
+        if let Some(p) = p {
+            grid.insert(p);
+            active.push(points.len());
+            points.push(p);
+        }
+    }
+
+    // The region filled as tightly as the spacing allows, keep a random subset to match the density
+    let (w, h) = (grid.width, grid.height);
+    let region = (0..w * h)
+        .filter(|i| {
+            let c = origin + vec2((i % w) as f32 + 0.5, (i / w) as f32 + 0.5) * cell;
+            inside(c)
+        })
+        .count() as f32
+        * cell
+        * cell;
+    let wanted = (region * density / 10000.0).round() as usize;
+    rng.shuffle(&mut points);
+    points.truncate(wanted);
+    points
+}
+
+#[cfg(test)]
+#[test]
+fn test_scatter() {
+    let bounds = Area {
+        left: 0.0,
+        right: 1000.0,
+        bottom: 0.0,
+        top: 500.0,
+    };
+    // Only the left half of the bounds, minus a hole
+    let inside = |p: Vec2| p.x < 500.0 && p.distance(vec2(250.0, 250.0)) > 100.0;
+    let points = scatter(&Rng::with_seed(7), &bounds, 20.0, 5.0, inside);
+
+    let expected = (500.0 * 500.0 - std::f32::consts::PI * 100.0 * 100.0) * 5.0 / 10000.0;
+    assert!((points.len() as f32 - expected).abs() < expected * 0.1);
+    for (i, a) in points.iter().enumerate() {
+        assert!(inside(*a));
+        for b in &points[i + 1..] {
+            assert!(a.distance(*b) >= 20.0);
+        }
+    }
+
+    let again = scatter(&Rng::with_seed(7), &bounds, 20.0, 5.0, inside);
+    assert_eq!(points, again);
+
+    let mut grid = PointGrid::new(&bounds, 30.0);
+    for p in &points {
+        grid.insert(*p);
+    }
+    // Nothing in the hole, a point found next to any of them, even from outside the bounds
+    assert!(!grid.any_within(vec2(250.0, 250.0), 90.0));
+    assert!(grid.any_within(points[0] + vec2(5.0, 0.0), 10.0));
+    let outside = vec2(-15.0, 0.0);
+    let near = points.iter().any(|p| p.distance(outside) < 20.0);
+    assert_eq!(grid.any_within(outside, 20.0), near);
+}
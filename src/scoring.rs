@@ -0,0 +1,263 @@
+use crate::tuning;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub const MERGE_POINTS: i32 = 100;
+pub const MULTI_MERGE_BONUS: i32 = 50;
+const COMBO_WINDOW: f64 = 4.0;
+const COMBO_MAX: u32 = 5;
+
+#[derive(Default)]
+pub struct Score {
+    pub score: i32,
+    pub time_end: f64,
+    pub combo: Combo,
+    pub breakdown: Vec<BreakdownLine>,
+    pub chicken_scared: bool,
+    /// Animals scared by another panicking animal rather than by a wolf
+    pub panic_spread: u32,
+    pub longest_panic_chain: u32,
+    /// Only rounds on a built-in map, outside of a playtest, go to the leaderboard
+    pub ranked: bool,
+}
+
+impl Score {
+    pub fn new(start: f64) -> Score {
+        Score {
+            score: 0,
+            time_end: start + 100.0,
+            combo: Combo::default(),
+            breakdown: vec![],
+            chicken_scared: false,
+            panic_spread: 0,
+            longest_panic_chain: 0,
+            ranked: false,
+        }
+    }
+
+    /// Adds points to the score and keeps track of where they came from
+    pub fn add(&mut self, rule: ScoreRule, points: i32) {
+        self.score += points;
+        match self.breakdown.iter_mut().find(|l| l.rule == rule) {
+            Some(line) => {
+                line.count += 1;
+                line.points += points;
+            }
+            None => self.breakdown.push(BreakdownLine {
+                rule,
+                count: 1,
+                points,
+            }),
+        }
+    }
+
+    /// Applies the bonuses that can only be decided once the round is over
+    pub fn finish_round(&mut self, rules: &ScoringRules) {
+        if !self.chicken_scared && rules.no_chicken_scared_bonus != 0 {
+            self.add(ScoreRule::NoChickenScared, rules.no_chicken_scared_bonus);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum ScoreRule {
+    Merge,
+    AnimalScared,
+    WolfRepelled,
+    AnimalLost,
+    PennedDogChicks,
+    NoChickenScared,
+    Upgrades,
+    Fences,
+}
+
+impl ScoreRule {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScoreRule::Merge => "Merges",
+            ScoreRule::AnimalScared => "Animals scared",
+            ScoreRule::WolfRepelled => "Wolves repelled",
+            ScoreRule::AnimalLost => "Animals lost",
+            ScoreRule::PennedDogChicks => "Penned DogChicks",
+            ScoreRule::Upgrades => "Upgrades bought",
+            ScoreRule::Fences => "Fences bought",
+            ScoreRule::NoChickenScared => "No chicken scared",
+        }
+    }
+
+    /// Whether counting how many times the rule was applied makes sense to the player
+    pub fn shows_count(&self) -> bool {
+        !matches!(
+            self,
+            ScoreRule::NoChickenScared | ScoreRule::PennedDogChicks
+        )
+    }
+}
+
+pub struct BreakdownLine {
+    pub rule: ScoreRule,
+    pub count: u32,
+    pub points: i32,
+}
+
+/// Tuning of every scoring rule apart from merges, a value of 0 disables the rule
+/// Read from `assets/tuning/scoring.ron`
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ScoringRules {
+    /// Points lost when a wolf scares an animal that was happily outside
+    pub scare_penalty: i32,
+    /// Points won when a wolf is clicked away while hunting
+    pub repel_reward: i32,
+    /// How close to its prey a wolf must be for the repel to count
+    pub repel_distance: f32,
+    /// Points lost when a wolf drags an animal back into the forest
+    pub lost_penalty: i32,
+    /// Points brought by each DogChick resting in its pen, every few seconds
+    pub dogchick_idle_points: i32,
+    /// Points won at the end of a round where no chicken was ever scared
+    pub no_chicken_scared_bonus: i32,
+}
+
+impl ScoringRules {
+    pub fn load() -> ScoringRules {
+        tuning::parse("scoring.ron", tuning::SCORING)
+    }
+}
+
+impl Default for ScoringRules {
+    fn default() -> Self {
+        ScoringRules {
+            scare_penalty: -20,
+            repel_reward: 50,
+            repel_distance: 250.0,
+            lost_penalty: -150,
+            dogchick_idle_points: 5,
+            no_chicken_scared_bonus: 500,
+        }
+    }
+}
+
+pub enum ScoreEvent {
+    Scared { chicken: bool },
+    WolfRepelled,
+    AnimalLost { chicken: bool },
+    DogChicksPenned { count: i32 },
+}
+
+pub fn apply_score_rules(
+    state: Res<GameState>,
+    rules: Res<ScoringRules>,
+    mut score: ResMut<Score>,
+    mut events: EventReader<ScoreEvent>,
+) {
+    if !matches!(*state, GameState::Playing) {
+        events.iter().for_each(drop);
+        return;
+    }
+
+    for ev in events.iter() {
+        match *ev {
+            ScoreEvent::Scared { chicken } => {
+                score.chicken_scared |= chicken;
+                if rules.scare_penalty != 0 {
+                    score.add(ScoreRule::AnimalScared, rules.scare_penalty);
+                }
+            }
+            ScoreEvent::WolfRepelled => {
+                if rules.repel_reward != 0 {
+                    score.add(ScoreRule::WolfRepelled, rules.repel_reward);
+                }
+            }
+            ScoreEvent::DogChicksPenned { count } => {
+                if rules.dogchick_idle_points != 0 {
+                    score.add(
+                        ScoreRule::PennedDogChicks,
+                        rules.dogchick_idle_points * count,
+                    );
+                }
+            }
+            ScoreEvent::AnimalLost { chicken } => {
+                score.chicken_scared |= chicken;
+                if rules.lost_penalty != 0 {
+                    score.add(ScoreRule::AnimalLost, rules.lost_penalty);
+                }
+            }
+        }
+    }
+}
+
+/// Merges happening less than COMBO_WINDOW seconds apart build up a multiplier
+#[derive(Default)]
+pub struct Combo {
+    pub count: u32,
+    pub until: f64,
+    pub best: u32,
+}
+
+impl Combo {
+    /// Registers a merge happening at time `now`, returns the new chain length
+    pub fn register(&mut self, now: f64) -> u32 {
+        if now > self.until {
+            self.count = 0;
+        }
+        self.count += 1;
+        self.until = now + COMBO_WINDOW;
+        self.best = self.best.max(self.count);
+        self.count
+    }
+
+    pub fn multiplier(&self) -> i32 {
+        self.count.clamp(1, COMBO_MAX) as i32
+    }
+
+    /// Fraction of the combo window left, 0 when the chain is broken
+    pub fn remaining(&self, now: f64) -> f32 {
+        ((self.until - now) / COMBO_WINDOW).clamp(0.0, 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_combo() {
+    let mut combo = Combo::default();
+    assert_eq!(combo.register(10.0), 1);
+    assert_eq!(combo.register(11.0), 2);
+    assert_eq!(combo.register(11.0 + COMBO_WINDOW * 0.5), 3);
+    assert_eq!(combo.multiplier(), 3);
+
+    for i in 0..5 {
+        combo.register(12.0 + i as f64 * 0.1);
+    }
+    assert_eq!(combo.multiplier(), COMBO_MAX as i32);
+
+    assert_eq!(combo.register(100.0), 1);
+    assert_eq!(combo.multiplier(), 1);
+    assert_eq!(combo.best, 8);
+    assert_eq!(combo.remaining(100.0 + COMBO_WINDOW * 2.0), 0.0);
+}
+
+#[cfg(test)]
+#[test]
+fn test_score_breakdown() {
+    let rules = ScoringRules::default();
+    let mut score = Score::new(0.0);
+    score.add(ScoreRule::Merge, 100);
+    score.add(ScoreRule::Merge, 200);
+    score.add(ScoreRule::AnimalScared, rules.scare_penalty);
+    score.finish_round(&rules);
+
+    assert_eq!(score.breakdown.len(), 3);
+    assert_eq!(score.breakdown[0].count, 2);
+    assert_eq!(score.breakdown[0].points, 300);
+    assert_eq!(
+        score.score,
+        300 + rules.scare_penalty + rules.no_chicken_scared_bonus
+    );
+
+    let mut score = Score::new(0.0);
+    score.chicken_scared = true;
+    score.finish_round(&rules);
+    assert_eq!(score.score, 0);
+}
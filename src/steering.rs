@@ -0,0 +1,97 @@
+use crate::entities::CarriedBy;
+use crate::physics::Airborne;
+use bevy::math::vec2;
+use bevy::prelude::*;
+
+/// Distance under which an agent starts slowing down before its objective
+const SLOWING_RADIUS: f32 = 25.0;
+/// How strongly agents push each other away, the push comes from `CollisionAvoid`
+const SEPARATION_WEIGHT: f32 = 400.0;
+
+#[derive(Component, Default)]
+pub struct Velocity(pub Vec2);
+
+/// How an agent is allowed to move, the AI systems only write `force`
+#[derive(Component)]
+pub struct Steering {
+    pub max_accel: f32,
+    /// Radians per second
+    pub turn_rate: f32,
+    pub force: Vec2,
+    /// Direction the agent faces, follows the velocity within the turn rate
+    pub heading: Vec2,
+}
+
+impl Steering {
+    pub fn new(max_accel: f32, turn_rate: f32) -> Steering {
+        Steering {
+            max_accel,
+            turn_rate,
+            force: Vec2::ZERO,
+            heading: vec2(1.0, 0.0),
+        }
+    }
+}
+
+/// Force to reach `objective` at `max_speed`, easing off close to it so the agent stops on it
+pub fn arrive(pos: Vec2, vel: Vec2, objective: Vec2, max_speed: f32) -> Vec2 {
+    let to_obj = objective - pos;
+    let dist = to_obj.length();
+    let speed = max_speed * (dist / SLOWING_RADIUS).min(1.0);
+    to_obj.normalize_or_zero() * speed - vel
+}
+
+/// Force pushing an agent away from its neighbours
+pub fn separation(getaway: Vec2) -> Vec2 {
+    getaway * SEPARATION_WEIGHT
+}
+
+/// Turns `from` towards `to` by at most `max_angle` radians, both being unit vectors
+fn turn_towards(from: Vec2, to: Vec2, max_angle: f32) -> Vec2 {
+    let angle = from.perp_dot(to).atan2(from.dot(to));
+    let (sin, cos) = angle.clamp(-max_angle, max_angle).sin_cos();
+    vec2(from.x * cos - from.y * sin, from.x * sin + from.y * cos)
+}
+
+pub fn steering_update(
+    time: Res<Time>,
+    mut qry: Query<
+        (&mut Transform, &mut Velocity, &mut Steering),
+        (Without<CarriedBy>, Without<Airborne>),
+    >,
+) {
+    let dt = time.delta_seconds();
+    for (mut trans, mut vel, mut steer) in qry.iter_mut() {
+        let force = steer.force.clamp_length_max(steer.max_accel);
+        let wanted = vel.0 + force * dt;
+        let speed = wanted.length();
+
+        if speed > 1.0 {
+            steer.heading = turn_towards(steer.heading, wanted / speed, steer.turn_rate * dt);
+            vel.0 = steer.heading * speed;
+        } else {
+            vel.0 = wanted;
+        }
+
+        trans.translation.x += vel.0.x * dt;
+        trans.translation.y += vel.0.y * dt;
+        steer.force = Vec2::ZERO;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_steering() {
+    let f = arrive(Vec2::ZERO, Vec2::ZERO, vec2(200.0, 0.0), 100.0);
+    assert_eq!(f, vec2(100.0, 0.0));
+
+    let f = arrive(Vec2::ZERO, vec2(50.0, 0.0), vec2(12.5, 0.0), 100.0);
+    assert_eq!(f, vec2(0.0, 0.0));
+
+    let h = turn_towards(vec2(1.0, 0.0), vec2(0.0, 1.0), 0.1);
+    assert!((h.length() - 1.0).abs() < 1e-5);
+    assert!((h.y.atan2(h.x) - 0.1).abs() < 1e-5);
+
+    let h = turn_towards(vec2(1.0, 0.0), vec2(0.0, -1.0), 10.0);
+    assert!(h.distance(vec2(0.0, -1.0)) < 1e-5);
+}
@@ -0,0 +1,108 @@
+use crate::maps::{Area, Map, Terrain};
+use bevy::prelude::*;
+
+/// Speed of animals walking in mud
+const MUD_SPEED: f32 = 0.5;
+/// Wolves farther than this don't see chickens in tall grass
+const GRASS_SIGHT: f32 = 120.0;
+/// Wandering chickens this close to a flower patch walk towards it
+const FLOWER_PULL_DISTANCE: f32 = 400.0;
+
+/// Mud, tall grass and flower patches of the current map, rebuilt when the map changes
+/// Rivers are not in it, their banks are colliders
+#[derive(Default)]
+pub struct TerrainMap {
+    mud: Vec<Area>,
+    grass: Vec<Area>,
+    flowers: Vec<Area>,
+}
+
+impl TerrainMap {
+    pub fn new(map: &Map) -> TerrainMap {
+        let mut terrain = TerrainMap {
+            flowers: map.flowers.iter().map(|f| f.area).collect(),
+            ..Default::default()
+        };
+        for zone in &map.terrain {
+            match zone {
+                Terrain::Mud(area) => terrain.mud.push(*area),
+                Terrain::TallGrass(area) => terrain.grass.push(*area),
+                Terrain::River { .. } => {}
+            }
+        }
+        terrain
+    }
+
+    /// Multiplier of the speed of an animal at `p`
+    pub fn speed(&self, p: Vec2) -> f32 {
+        if self.mud.iter().any(|a| a.contains(p)) {
+            MUD_SPEED
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether a chicken at `p` can't be seen by a wolf at `from`
+    pub fn hides(&self, p: Vec2, from: Vec2) -> bool {
+        p.distance(from) > GRASS_SIGHT && self.grass.iter().any(|a| a.contains(p))
+    }
+
+    /// Middle of the closest flower patch within reach of `p`
+    pub fn flowers_near(&self, p: Vec2) -> Option<Vec2> {
+        self.flowers
+            .iter()
+            .map(|a| a.center())
+            .filter(|c| c.distance(p) < FLOWER_PULL_DISTANCE)
+            .min_by(|a, b| a.distance(p).total_cmp(&b.distance(p)))
+    }
+}
+
+pub fn update_terrain(map: Res<Map>, mut terrain: ResMut<TerrainMap>) {
+    if map.is_changed() {
+        *terrain = TerrainMap::new(&map);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_terrain() {
+    use crate::maps::MapList;
+    use bevy::math::vec2;
+
+    let maps = MapList::builtin();
+    let map = maps.maps.iter().find(|m| m.name == "Riverside").unwrap();
+    let terrain = TerrainMap::new(map);
+
+    let mud = terrain.mud[0].center();
+    assert_eq!(terrain.speed(mud), MUD_SPEED);
+    assert_eq!(terrain.speed(vec2(0.0, 800.0)), 1.0);
+
+    let grass = terrain.grass[0].center();
+    assert!(terrain.hides(grass, grass + vec2(0.0, 300.0)));
+    assert!(!terrain.hides(grass, grass + vec2(0.0, 50.0)));
+    assert!(!terrain.hides(mud, mud + vec2(0.0, 300.0)));
+
+    let patch = terrain.flowers[0].center();
+    assert_eq!(terrain.flowers_near(patch + vec2(100.0, 0.0)), Some(patch));
+
+    // Only the bridges cross the river
+    let river = map
+        .terrain
+        .iter()
+        .find_map(|t| match t {
+            Terrain::River { area, bridges } => Some((*area, bridges[0])),
+            _ => None,
+        })
+        .unwrap();
+    let (area, bridge) = river;
+    let shapes = map.shapes();
+    let blocked = |x: f32| {
+        let (a, b) = (vec2(x, area.bottom - 20.0), vec2(x, area.top + 20.0));
+        (0..=20).any(|i| {
+            let p = a.lerp(b, i as f32 / 20.0);
+            shapes.iter().any(|s| s.push_out(p, 10.0).is_some())
+        })
+    };
+    assert!(blocked(bridge + 200.0));
+    assert!(!blocked(bridge));
+}
@@ -0,0 +1,325 @@
+use crate::entities::{
+    CarriedBy, Chicken, Dog, Looker, LookerLocation, LookerState, NNTree, HAND_SIZE,
+};
+use crate::gfx::{Action, Inputs, MouseProj};
+use crate::maps::Map;
+use crate::physics::Airborne;
+use crate::GameState;
+use bevy::math::{vec3, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy_spatial::SpatialAccess;
+
+pub(crate) const WHISTLE_RADIUS: f32 = 600.0;
+const WHISTLE_COOLDOWN: f64 = 10.0;
+const WHISTLE_DURATION: f64 = 3.0;
+const GRAB_RADIUS: f32 = 30.0;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Tool {
+    /// Happy animals follow the hand
+    Attract,
+    /// Happy animals run away from the hand
+    Repel,
+    /// Calls every dog around to the clicked spot
+    Whistle,
+    /// Pick an animal up and drop it elsewhere
+    Grab,
+    /// Drag to build a fence
+    Fence,
+}
+
+pub const ALL_TOOLS: [Tool; 5] = [
+    Tool::Attract,
+    Tool::Repel,
+    Tool::Whistle,
+    Tool::Grab,
+    Tool::Fence,
+];
+
+impl Tool {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Attract => "Attract",
+            Tool::Repel => "Repel",
+            Tool::Whistle => "Whistle",
+            Tool::Grab => "Grab",
+            Tool::Fence => "Fence",
+        }
+    }
+
+    pub fn action(&self) -> Action {
+        match self {
+            Tool::Attract => Action::ToolAttract,
+            Tool::Repel => Action::ToolRepel,
+            Tool::Whistle => Action::ToolWhistle,
+            Tool::Grab => Action::ToolGrab,
+            Tool::Fence => Action::ToolFence,
+        }
+    }
+
+    /// Drawn around the cursor, tinted and scaled by `cursor`
+    fn cursor_texture(&self) -> &'static str {
+        match self {
+            Tool::Attract => "cursor_attract.png",
+            Tool::Repel => "cursor_repel.png",
+            Tool::Whistle => "cursor_whistle.png",
+            Tool::Grab => "cursor_grab.png",
+            Tool::Fence => "cursor_fence.png",
+        }
+    }
+
+    /// Played when the tool is picked, and when it is used for the ones used with a click
+    pub fn sound(&self) -> &'static str {
+        match self {
+            Tool::Attract => "attract.wav",
+            Tool::Repel => "repel.wav",
+            Tool::Whistle => "whistle.wav",
+            Tool::Grab => "grab.wav",
+            Tool::Fence => "fence.wav",
+        }
+    }
+
+    /// Tint and radius of the ring drawn around the cursor
+    fn cursor(&self) -> (Color, f32) {
+        match self {
+            Tool::Attract => (Color::rgba(0.5, 1.0, 0.5, 0.6), HAND_SIZE),
+            Tool::Repel => (Color::rgba(1.0, 0.4, 0.3, 0.6), HAND_SIZE),
+            Tool::Whistle => (Color::rgba(0.5, 0.7, 1.0, 0.3), WHISTLE_RADIUS),
+            Tool::Grab => (Color::rgba(1.0, 0.9, 0.4, 0.8), GRAB_RADIUS),
+            Tool::Fence => (Color::rgba(0.6, 0.35, 0.15, 0.8), 10.0),
+        }
+    }
+
+    fn cooldown(&self) -> f64 {
+        match self {
+            Tool::Whistle => WHISTLE_COOLDOWN,
+            _ => 0.0,
+        }
+    }
+}
+
+pub struct ToolState {
+    pub current: Tool,
+    pub grabbed: Option<Entity>,
+    ready_at: [f64; ALL_TOOLS.len()],
+    last_hand: Vec2,
+    /// Smoothed cursor velocity, given to animals when they are thrown
+    hand_vel: Vec2,
+}
+
+impl Default for ToolState {
+    fn default() -> Self {
+        ToolState {
+            current: Tool::Attract,
+            grabbed: None,
+            ready_at: [0.0; ALL_TOOLS.len()],
+            last_hand: Vec2::ZERO,
+            hand_vel: Vec2::ZERO,
+        }
+    }
+}
+
+impl ToolState {
+    /// Fraction of the cooldown still to wait, 0 when the tool can be used
+    pub fn cooldown_left(&self, tool: Tool, now: f64) -> f32 {
+        if tool.cooldown() == 0.0 {
+            return 0.0;
+        }
+        ((self.ready_at[tool as usize] - now) / tool.cooldown()).clamp(0.0, 1.0) as f32
+    }
+}
+
+/// Put on dogs answering a whistle, they run to `to` until the call is over
+#[derive(Component)]
+pub struct Called {
+    pub to: Vec2,
+    pub until: f64,
+}
+
+/// Put on the animal currently held by the player, it is also `Airborne` until it lands
+#[derive(Component)]
+pub struct Grabbed;
+
+#[derive(Component)]
+pub struct ToolCursor;
+
+/// The tools are only used on the meadow of a round, not in the menus or the editor
+fn tools_active(state: &GameState) -> bool {
+    matches!(state, GameState::Playing | GameState::EndGamePlaying)
+}
+
+pub fn spawn_tool_cursor(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn()
+        .insert(ToolCursor)
+        .insert_bundle(SpriteBundle {
+            texture: asset_server.load(Tool::Attract.cursor_texture()),
+            ..Default::default()
+        });
+}
+
+pub fn tool_select(
+    inputs: Res<Inputs>,
+    state: Res<GameState>,
+    mut tools: ResMut<ToolState>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+) {
+    if !tools_active(&state) {
+        return;
+    }
+    for tool in ALL_TOOLS {
+        if inputs.just_pressed.contains(&tool.action()) && tools.current != tool {
+            tools.current = tool;
+            audio.play_with_settings(
+                asset_server.load(tool.sound()),
+                PlaybackSettings {
+                    repeat: false,
+                    volume: 0.3,
+                    speed: 1.0,
+                },
+            );
+        }
+    }
+}
+
+pub fn tool_use(
+    mut commands: Commands,
+    time: Res<Time>,
+    inputs: Res<Inputs>,
+    state: Res<GameState>,
+    map: Res<Map>,
+    hand: Res<MouseProj>,
+    tree: Res<NNTree>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    mut tools: ResMut<ToolState>,
+    lookers: Query<
+        (&Looker, Option<&Chicken>),
+        (Without<CarriedBy>, Or<(With<Dog>, With<Chicken>)>),
+    >,
+    mut grabbed: Query<(&mut Transform, &mut Airborne), With<Grabbed>>,
+) {
+    let now = time.seconds_since_startup();
+
+    if time.delta_seconds() > 0.0 {
+        let vel = (hand.0 - tools.last_hand) / time.delta_seconds();
+        tools.hand_vel = tools.hand_vel.lerp(vel, 0.5);
+    }
+    tools.last_hand = hand.0;
+
+    let active = tools_active(&state);
+    if let Some(ent) = tools.grabbed {
+        // The held animal is thrown when the round ends too
+        let held =
+            active && inputs.pressed.contains(&Action::ClickLeft) && tools.current == Tool::Grab;
+        match grabbed.get_mut(ent) {
+            Ok((mut trans, _)) if held => {
+                trans.translation.x = hand.0.x;
+                trans.translation.y = hand.0.y.max(map.meadow.bottom + 20.0);
+            }
+            Ok((_, mut air)) => {
+                air.impulse(tools.hand_vel, Airborne::throw_lift(tools.hand_vel));
+                commands.entity(ent).remove::<Grabbed>();
+                tools.grabbed = None;
+            }
+            Err(_) => tools.grabbed = None,
+        }
+        return;
+    }
+
+    if !active
+        || !inputs.just_pressed.contains(&Action::ClickLeft)
+        || tools.ready_at[tools.current as usize] > now
+    {
+        return;
+    }
+
+    match tools.current {
+        Tool::Attract | Tool::Repel | Tool::Fence => {}
+        Tool::Whistle => {
+            tools.ready_at[Tool::Whistle as usize] = now + WHISTLE_COOLDOWN;
+            for (_, e) in tree.within_distance(hand.0.extend(0.0), WHISTLE_RADIUS) {
+                match lookers.get(e) {
+                    Ok((l, None))
+                        if l.location == LookerLocation::Outside
+                            && matches!(l.state, LookerState::Happy) => {}
+                    _ => continue,
+                }
+                commands.entity(e).insert(Called {
+                    to: hand.0,
+                    until: now + WHISTLE_DURATION,
+                });
+            }
+            audio.play_with_settings(
+                asset_server.load(Tool::Whistle.sound()),
+                PlaybackSettings {
+                    repeat: false,
+                    volume: 1.0,
+                    speed: 1.0,
+                },
+            );
+        }
+        Tool::Grab => {
+            let mut nearest = None;
+            let mut neares_dist = f32::INFINITY;
+            for (pos, e) in tree.within_distance(hand.0.extend(0.0), GRAB_RADIUS) {
+                let d = pos.xy().distance_squared(hand.0);
+                match lookers.get(e) {
+                    Ok((l, chicken))
+                        if l.location == LookerLocation::Outside && d < neares_dist =>
+                    {
+                        neares_dist = d;
+                        nearest = Some((e, chicken.is_some()));
+                    }
+                    _ => {}
+                }
+            }
+            if let Some((e, chicken)) = nearest {
+                commands.entity(e).insert(Grabbed).insert(Airborne::held());
+                tools.grabbed = Some(e);
+                audio.play_with_settings(
+                    asset_server.load(if chicken {
+                        "chicken1.ogg"
+                    } else {
+                        "dogbark1.ogg"
+                    }),
+                    PlaybackSettings {
+                        repeat: false,
+                        volume: 1.0,
+                        speed: 1.3,
+                    },
+                );
+            }
+        }
+    }
+}
+
+pub fn tool_cursor_update(
+    hand: Res<MouseProj>,
+    tools: Res<ToolState>,
+    state: Res<GameState>,
+    asset_server: Res<AssetServer>,
+    mut cursor: Query<
+        (
+            &mut Transform,
+            &mut Sprite,
+            &mut Handle<Image>,
+            &mut Visibility,
+        ),
+        With<ToolCursor>,
+    >,
+    mut shown: Local<Option<Tool>>,
+) {
+    let (color, radius) = tools.current.cursor();
+    for (mut trans, mut sprite, mut texture, mut vis) in cursor.iter_mut() {
+        vis.is_visible = tools_active(&state);
+        trans.translation = vec3(hand.0.x, hand.0.y, 0.9);
+        trans.scale = Vec3::splat(radius / 64.0);
+        sprite.color = color;
+        if *shown != Some(tools.current) {
+            *texture = asset_server.load(tools.current.cursor_texture());
+        }
+    }
+    *shown = Some(tools.current);
+}
@@ -0,0 +1,36 @@
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+
+/// Tunables shipped with the game, in `assets/tuning`
+pub const SCORING: &str = include_str!("../assets/tuning/scoring.ron");
+pub const WOLVES: &str = include_str!("../assets/tuning/wolves.ron");
+
+/// Reads tunables written in RON, falling back to the defaults when the text is invalid
+/// Fields missing from the text keep their default as long as the type is `#[serde(default)]`
+pub fn parse<T: DeserializeOwned + Default>(name: &str, text: &str) -> T {
+    ron::from_str(text).unwrap_or_else(|e| {
+        warn!("invalid tuning {}: {}", name, e);
+        T::default()
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn test_tuning() {
+    use crate::archetypes::WolfKind;
+    use crate::scoring::ScoringRules;
+
+    assert!(ron::from_str::<ScoringRules>(SCORING).is_ok());
+    let rules: ScoringRules = parse("partial", "(repel_reward: 7)");
+    assert_eq!(rules.repel_reward, 7);
+    assert_eq!(rules.lost_penalty, ScoringRules::default().lost_penalty);
+    let rules: ScoringRules = parse("broken", "(repel_reward: ");
+    assert_eq!(rules.repel_reward, ScoringRules::default().repel_reward);
+
+    // The fallback matches the shipped file
+    let shipped: crate::archetypes::WolfArchetypes = ron::from_str(WOLVES).unwrap();
+    assert_eq!(shipped, Default::default());
+    assert_eq!(WolfKind::Regular.stats().stamina, f64::INFINITY);
+    assert_eq!(WolfKind::Runner.stats().stamina, 4.0);
+    assert!(WolfKind::Sneaky.stats().tint.a() < 1.0);
+}
@@ -1,4 +1,13 @@
-use crate::entities::{spawn_chicken, spawn_dog, spawn_wolf, start_game};
+use crate::entities::{release_dogchick, spawn_chicken, spawn_dog, start_game, DogChick};
+use crate::fences::{FenceBudget, FenceRules};
+use crate::guard::Patrol;
+use crate::editor::MapEditor;
+use crate::maps::{Map, MapList};
+use crate::pack::spawn_pack;
+use crate::raid::{RaidRules, ALL_DIFFICULTIES};
+use crate::tools::{Tool, ToolState, ALL_TOOLS};
+use crate::upgrades::{Upgrades, ALL_UPGRADES};
+use crate::waves::{SpawnEdge, WaveDirector};
 use crate::{DespawnQry, Score};
 use bevy::prelude::*;
 use bevy_egui::egui::Align;
@@ -19,6 +28,8 @@ pub enum GameState {
     },
     Playing,
     EndGamePlaying,
+    /// Building a map with the editor panels
+    Editor,
     EndGame {
         score_sent: bool,
         leaderboard_load: bool,
@@ -127,7 +138,16 @@ pub(crate) fn ui_example(
     mut egui_context: ResMut<EguiContext>,
     mut state: ResMut<GameState>,
     mut score: ResMut<Score>,
+    mut director: ResMut<WaveDirector>,
+    mut upgrades: ResMut<Upgrades>,
+    mut tools: ResMut<ToolState>,
+    mut fences: ResMut<FenceBudget>,
+    fence_rules: Res<FenceRules>,
+    mut raids: ResMut<RaidRules>,
+    // Grouped to stay within the system parameter limit
+    (mut map, mut maps, editor): (ResMut<Map>, ResMut<MapList>, Res<MapEditor>),
     qry: DespawnQry,
+    penned: Query<Entity, (With<DogChick>, Without<Patrol>)>,
 ) {
     match *state {
         GameState::Menu {
@@ -145,7 +165,64 @@ pub(crate) fn ui_example(
                     ui.vertical_centered(|ui| {
                         if ui.button("Start Game").clicked() {
                             newstate = Some(GameState::Playing);
-                            start_game(qry, &mut commands, &asset_server, &time, &mut score);
+                            start_game(
+                                qry,
+                                &mut commands,
+                                &asset_server,
+                                &time,
+                                &mut score,
+                                &mut director,
+                                &mut upgrades,
+                                &mut fences,
+                                &fence_rules,
+                                &mut map,
+                            );
+                            score.ranked = !maps.random && maps.is_builtin(&map);
+                        }
+                        if ui.button("Map editor").clicked() {
+                            newstate = Some(GameState::Editor);
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Difficulty:");
+                            for difficulty in ALL_DIFFICULTIES {
+                                if ui
+                                    .radio(raids.difficulty == difficulty, difficulty.label())
+                                    .on_hover_text("Hungry packs raid the pens in Normal and Hard")
+                                    .clicked()
+                                {
+                                    *raids = RaidRules::new(difficulty);
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Map:");
+                            for i in 0..maps.maps.len() {
+                                let name = maps.maps[i].name.clone();
+                                let checked = !maps.random && maps.selected == i;
+                                if ui.radio(checked, name).clicked() && !checked {
+                                    maps.selected = i;
+                                    maps.random = false;
+                                    *map = maps.maps[i].clone();
+                                }
+                            }
+                            if ui.radio(maps.random, "Random").clicked() && !maps.random {
+                                maps.random = true;
+                                maps.next_round(&mut map);
+                            }
+                        });
+                        if maps.random {
+                            ui.horizontal(|ui| {
+                                ui.label("Seed:");
+                                let edit = ui
+                                    .text_edit_singleline(&mut maps.seed_input)
+                                    .on_hover_text("Leave empty to get a new map every round");
+                                if edit.changed() {
+                                    maps.next_round(&mut map);
+                                }
+                            });
+                        }
+                        if let Some(seed) = map.seed {
+                            ui.label(format!("Map seed: {}", seed));
                         }
                         if !*leaderboard_load {
                             *leaderboard_load = true;
@@ -208,9 +285,78 @@ pub(crate) fn ui_example(
 
                     ui.with_layout(egui::Layout::top_down(Align::Center), |ui| {
                         ui.label(format!("Time left: {}s", time_left as i64));
+                        if let Some((text, _)) = &director.announcement {
+                            ui.colored_label(egui::Color32::from_rgb(200, 60, 40), text);
+                        }
+
+                        ui.horizontal(|ui| {
+                            for (i, tool) in ALL_TOOLS.into_iter().enumerate() {
+                                let text = format!("{} {}", i + 1, tool.label());
+                                if ui.selectable_label(tools.current == tool, text).clicked() {
+                                    tools.current = tool;
+                                }
+                            }
+                        });
+                        let cooldown = tools.cooldown_left(tools.current, time.seconds_since_startup());
+                        if cooldown > 0.0 {
+                            ui.add(egui::ProgressBar::new(1.0 - cooldown).desired_width(150.0));
+                        }
+                        if tools.current == Tool::Fence {
+                            ui.label(format!("Free fence left: {}", fences.left as i32))
+                                .on_hover_text("Longer fences cost points");
+                        }
+
+                        if let Some(ent) = penned.iter().next() {
+                            if ui
+                                .button("Release a DogChick")
+                                .on_hover_text("It will chase wolves, but won't earn points in the pen anymore")
+                                .clicked()
+                            {
+                                release_dogchick(&mut commands, &map, ent);
+                            }
+                        }
+
+                        ui.collapsing("Upgrades", |ui| {
+                            for upgrade in ALL_UPGRADES {
+                                let level = upgrades.level(upgrade);
+                                let text = if level < upgrade.max_level() {
+                                    format!(
+                                        "{} {}/{} ({} pts)",
+                                        upgrade.label(),
+                                        level,
+                                        upgrade.max_level(),
+                                        upgrade.cost(level)
+                                    )
+                                } else {
+                                    format!("{} (max)", upgrade.label())
+                                };
+                                if ui
+                                    .add_enabled(
+                                        upgrades.can_buy(upgrade, &score),
+                                        egui::Button::new(text),
+                                    )
+                                    .clicked()
+                                {
+                                    upgrades.buy(upgrade, &mut score);
+                                }
+                            }
+                        });
+                        ui.label(format!("Score: {}", score.score));
+                        if let Some(seed) = map.seed {
+                            ui.label(format!("Map seed: {}", seed));
+                        }
+
+                        let remaining = score.combo.remaining(time.seconds_since_startup());
+                        if remaining > 0.0 && score.combo.count > 1 {
+                            ui.add(
+                                egui::ProgressBar::new(remaining)
+                                    .text(format!("Combo x{}", score.combo.multiplier())),
+                            );
+                        }
                     })
                 });
         }
+        GameState::Editor => {}
         GameState::EndGamePlaying => {
             egui::Window::new("The End")
                 .title_bar(false)
@@ -220,19 +366,35 @@ pub(crate) fn ui_example(
                 .show(egui_context.ctx_mut(), move |ui| {
                     if ui.button("Restart").clicked() {
                         *state = GameState::Playing;
-                        start_game(qry, &mut commands, &asset_server, &time, &mut score);
+                        // A playtest restarts on the edited map
+                        if !editor.playtesting {
+                            maps.next_round(&mut map);
+                        }
+                        start_game(
+                            qry,
+                            &mut commands,
+                            &asset_server,
+                            &time,
+                            &mut score,
+                            &mut director,
+                            &mut upgrades,
+                            &mut fences,
+                            &fence_rules,
+                            &mut map,
+                        );
                     }
 
                     if ui.button("More chickens & dogs").clicked() {
                         for _ in 0..10 {
-                            spawn_dog(&mut commands, &asset_server);
-                            spawn_chicken(&mut commands, &asset_server);
+                            spawn_dog(&mut commands, &asset_server, &map);
+                            spawn_chicken(&mut commands, &asset_server, &map);
                         }
                     }
 
                     if ui.button("More wolves").clicked() {
-                        for _ in 0..10 {
-                            spawn_wolf(&mut commands, &asset_server);
+                        for _ in 0..2 {
+                            let center = SpawnEdge::Anywhere.pick(&map.spawns.wolves);
+                            spawn_pack(&mut commands, &asset_server, &map, center, 5);
                         }
                     }
                 });
@@ -256,6 +418,27 @@ pub(crate) fn ui_example(
                 .show(egui_context.ctx_mut(), |ui| {
                     ui.vertical_centered(|ui| {
                         ui.label(format!("You scored: {}", score.score));
+                        egui::Grid::new("score_breakdown").show(ui, |ui| {
+                            for line in score.breakdown.iter() {
+                                ui.label(line.rule.label());
+                                if line.rule.shows_count() {
+                                    ui.label(format!("x{}", line.count));
+                                } else {
+                                    ui.label("");
+                                }
+                                ui.label(format!("{:+}", line.points));
+                                ui.end_row();
+                            }
+                        });
+                        if score.combo.best > 1 {
+                            ui.label(format!("Best combo: {} merges", score.combo.best));
+                        }
+                        if score.panic_spread > 0 {
+                            ui.label(format!(
+                                "Panic spread to {} animals, longest chain: {}",
+                                score.panic_spread, score.longest_panic_chain
+                            ));
+                        }
                         ui.label(format!("Good job!"));
 
                         if error.load(Ordering::SeqCst) {
@@ -277,7 +460,11 @@ pub(crate) fn ui_example(
 
                             if !*score_sent
                                 && ui
-                                .add_enabled(username.len() > 0, egui::Button::new("Send score"))
+                                .add_enabled(
+                                    score.ranked && username.len() > 0,
+                                    egui::Button::new("Send score"),
+                                )
+                                .on_disabled_hover_text("Only rounds on built-in maps are ranked")
                                 .clicked()
                             {
                                 *score_sent = true;
@@ -366,7 +553,21 @@ pub(crate) fn ui_example(
                         }
 
                         if ui.button("Restart").clicked() {
-                            start_game(qry, &mut commands, &asset_server, &time, &mut score);
+                            if !editor.playtesting {
+                                maps.next_round(&mut map);
+                            }
+                            start_game(
+                                qry,
+                                &mut commands,
+                                &asset_server,
+                                &time,
+                                &mut score,
+                                &mut director,
+                                &mut upgrades,
+                                &mut fences,
+                                &fence_rules,
+                                &mut map,
+                            );
                             newstate = Some(GameState::Playing);
                         }
 
@@ -0,0 +1,60 @@
+use crate::scoring::{Score, ScoreRule};
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Upgrade {
+    /// Calm scared animals faster by hovering them
+    SoothingTouch,
+}
+
+pub const ALL_UPGRADES: [Upgrade; 1] = [Upgrade::SoothingTouch];
+
+impl Upgrade {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Upgrade::SoothingTouch => "Soothing touch",
+        }
+    }
+
+    pub fn max_level(&self) -> u32 {
+        match self {
+            Upgrade::SoothingTouch => 3,
+        }
+    }
+
+    /// Points needed to go from `level` to the next one
+    pub fn cost(&self, level: u32) -> i32 {
+        match self {
+            Upgrade::SoothingTouch => 300 * (level as i32 + 1),
+        }
+    }
+}
+
+/// Upgrades bought with points during the current round
+#[derive(Default)]
+pub struct Upgrades {
+    levels: [u32; ALL_UPGRADES.len()],
+}
+
+impl Upgrades {
+    pub fn level(&self, upgrade: Upgrade) -> u32 {
+        self.levels[upgrade as usize]
+    }
+
+    pub fn can_buy(&self, upgrade: Upgrade, score: &Score) -> bool {
+        let level = self.level(upgrade);
+        level < upgrade.max_level() && score.score >= upgrade.cost(level)
+    }
+
+    pub fn buy(&mut self, upgrade: Upgrade, score: &mut Score) {
+        if !self.can_buy(upgrade, score) {
+            return;
+        }
+        score.add(ScoreRule::Upgrades, -upgrade.cost(self.level(upgrade)));
+        self.levels[upgrade as usize] += 1;
+    }
+
+    /// Seconds the hand must stay over a scared animal to calm it
+    pub fn calm_hover_time(&self) -> f64 {
+        1.5 - 0.4 * self.level(Upgrade::SoothingTouch) as f64
+    }
+}
@@ -0,0 +1,165 @@
+use crate::maps::{Area, Map};
+use crate::pack::{spawn_pack, Pack};
+use crate::GameState;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Where a wave comes out of the forest
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum SpawnEdge {
+    Left,
+    Right,
+    Top,
+    Anywhere,
+}
+
+impl SpawnEdge {
+    pub fn pick(&self, forest: &Area) -> Vec2 {
+        let x = forest.left + fastrand::f32() * (forest.right - forest.left);
+        // Away from the meadow and the back of the forest, closer to them in a thin forest
+        let height = forest.top - forest.bottom;
+        let (low, high) = (150.0_f32.min(height * 0.4), 50.0_f32.min(height * 0.2));
+        let y = forest.bottom + low + fastrand::f32() * (height - low - high);
+        match self {
+            SpawnEdge::Left => vec2(forest.left, y),
+            SpawnEdge::Right => vec2(forest.right, y),
+            SpawnEdge::Top => vec2(x, forest.top),
+            SpawnEdge::Anywhere => vec2(x, y),
+        }
+    }
+}
+
+/// A wave of the script of a map, written `Wave(at: 15.0, packs: [3], edge: Left)` in map files
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Wave {
+    /// Seconds since the start of the round
+    pub at: f64,
+    /// Size of each pack of the wave
+    pub packs: Vec<usize>,
+    pub edge: SpawnEdge,
+    /// Shown in the HUD when the wave arrives
+    #[serde(default)]
+    pub announce: Option<String>,
+}
+
+impl Wave {
+    fn new(at: f64, packs: &[usize], edge: SpawnEdge) -> Wave {
+        Wave {
+            at,
+            packs: packs.to_vec(),
+            edge,
+            announce: None,
+        }
+    }
+
+    fn announced(mut self, text: &str) -> Wave {
+        self.announce = Some(text.to_string());
+        self
+    }
+}
+
+/// The waves of a 100 seconds round, sorted by time, for maps without their own script
+pub fn default_script() -> Vec<Wave> {
+    use SpawnEdge::*;
+    vec![
+        Wave::new(0.0, &[3], Anywhere),
+        Wave::new(15.0, &[3], Left),
+        Wave::new(30.0, &[4], Right),
+        Wave::new(50.0, &[4, 3], Top).announced("The pack is coming!"),
+        Wave::new(70.0, &[3], Left),
+        Wave::new(85.0, &[5], Top).announced("Last howl!"),
+    ]
+}
+
+#[derive(Default)]
+pub struct WaveDirector {
+    pub script: Vec<Wave>,
+    next: usize,
+    round_start: f64,
+    running: bool,
+    pub announcement: Option<(String, f64)>,
+}
+
+impl WaveDirector {
+    pub fn start(&mut self, script: Vec<Wave>, now: f64) {
+        *self = WaveDirector {
+            script,
+            round_start: now,
+            running: true,
+            ..Default::default()
+        };
+    }
+}
+
+// Spawn the waves as they come due, and send everyone back to the forest once the round is over
+pub fn wave_director(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    state: Res<GameState>,
+    map: Res<Map>,
+    mut director: ResMut<WaveDirector>,
+    mut packs: Query<&mut Pack>,
+) {
+    if !director.running {
+        return;
+    }
+
+    let now = time.seconds_since_startup();
+
+    if !matches!(*state, GameState::Playing) {
+        director.running = false;
+        director.announcement = None;
+        for mut pack in packs.iter_mut() {
+            pack.target = None;
+            pack.retreat_until = f64::INFINITY;
+        }
+        return;
+    }
+
+    while let Some(wave) = director.script.get(director.next) {
+        if now - director.round_start < wave.at {
+            break;
+        }
+        for &size in &wave.packs {
+            spawn_pack(
+                &mut commands,
+                &asset_server,
+                &map,
+                wave.edge.pick(&map.spawns.wolves),
+                size,
+            );
+        }
+        if let Some(text) = &wave.announce {
+            director.announcement = Some((text.clone(), now + 3.0));
+        }
+        director.next += 1;
+    }
+
+    if matches!(director.announcement, Some((_, until)) if until < now) {
+        director.announcement = None;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_spawn_edge() {
+    let thin = Area {
+        left: -500.0,
+        right: 500.0,
+        bottom: 300.0,
+        top: 400.0,
+    };
+    for edge in [
+        SpawnEdge::Left,
+        SpawnEdge::Right,
+        SpawnEdge::Top,
+        SpawnEdge::Anywhere,
+    ] {
+        for _ in 0..50 {
+            let p = edge.pick(&thin);
+            assert!(p.y >= thin.bottom && p.y <= thin.top);
+        }
+    }
+}